@@ -0,0 +1,1102 @@
+//! Assembler for Octo source code
+//!
+//! Supports labels, `:const`, `:alias`, `:macro`, `:call`, `:byte`,
+//! `loop`/`while`/`again`, and `if` with both `then` and `begin`/`else`/`end`,
+//! along with the SUPER-CHIP and XO-CHIP statements.
+//! The output is a ROM which can be loaded with [`Emulator::load_script`].
+//!
+//! As in Octo, the ROM starts with a jump to the `main` label.
+//!
+//! [`Emulator::load_script`]: crate::Emulator::load_script
+
+mod tokens;
+
+use std::collections::HashMap;
+use std::{error::Error, fmt};
+
+use crate::emulator::{Instruction, SCRIPT_ADDR, XO_CHIP_MEMORY_SIZE};
+use tokens::{parse_number, parse_register, tokenize, Token};
+
+// highest address a 12 bit operand can hold
+const MAX_ADDR: i32 = 0xFFF;
+
+// highest address F000 NNNN can hold
+const MAX_LONG_ADDR: i32 = 0xFFFF;
+
+// how deeply macros can expand inside each other before assuming a macro is recursive
+const MAX_MACRO_DEPTH: usize = 64;
+
+/// An error found while assembling, with the line it was found on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize, // 1 based line number
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assembles Octo source code into a ROM
+///
+/// # Arguments
+///
+/// * `source` - Octo source code
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler::new(tokenize(source));
+
+    asm.run()?;
+    asm.finish()
+}
+
+// a reference to a label which is filled in once every label is known
+struct Fixup {
+    addr: usize, // address of the instruction to patch
+    label: String,
+    line: usize,
+    long: bool, // whether this is the full 16 bit word after F000 instead of a 12 bit operand
+}
+
+// an open control flow block
+enum Flow {
+    Loop {
+        start: usize,
+        breaks: Vec<usize>, // jumps out of the loop made by `while`
+        line: usize,
+    },
+    If {
+        jump: usize, // jump to the `else` or `end`
+        line: usize,
+    },
+    Else {
+        jump: usize, // jump from the end of the `if` body to `end`
+        line: usize,
+    },
+}
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+// a condition for `if` and `while`, as the two ways it can be skipped
+struct Condition {
+    skip_if_false: Instruction, // runs the next instruction only when the condition holds
+    skip_if_true: Instruction,  // runs the next instruction only when the condition fails
+}
+
+// an operand that is either known now or a label that may be defined later
+enum Address {
+    Known(u16),
+    Label(String),
+}
+
+struct Assembler {
+    tokens: Vec<Token>,
+    pos: usize, // index of the next token
+    rom: Vec<u8>,
+    labels: HashMap<String, usize>,
+    constants: HashMap<String, i32>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    flow: Vec<Flow>,
+}
+
+impl Assembler {
+    fn new(tokens: Vec<Token>) -> Self {
+        let mut asm = Self {
+            tokens,
+            pos: 0,
+            rom: Vec::new(),
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            flow: Vec::new(),
+        };
+
+        // the program starts with a jump to main, which can't fail since main isn't known yet
+        let _ = asm.emit_jump_to(
+            Instruction::Jump { nnn: 0 },
+            Address::Label("main".into()),
+            1,
+        );
+
+        asm
+    }
+
+    // the address the next byte will be written to
+    fn here(&self) -> usize {
+        SCRIPT_ADDR + self.rom.len()
+    }
+
+    fn error<T>(&self, line: usize, message: impl Into<String>) -> Result<T, AsmError> {
+        Err(AsmError {
+            line,
+            message: message.into(),
+        })
+    }
+
+    fn next(&mut self) -> Result<Token, AsmError> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => {
+                let line = self.tokens.last().map_or(1, |token| token.line);
+                self.error(line, "unexpected end of file")
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| token.text.as_str())
+    }
+
+    // consumes the next token, which must be `text`
+    fn expect(&mut self, text: &str) -> Result<Token, AsmError> {
+        let token = self.next()?;
+
+        if token.text != text {
+            return self.error(
+                token.line,
+                format!("expected `{text}`, found `{}`", token.text),
+            );
+        }
+
+        Ok(token)
+    }
+
+    fn emit(&mut self, instruction: Instruction) {
+        self.rom.extend(instruction.encode().to_be_bytes());
+    }
+
+    // emits an instruction with a 12 bit address operand which may not be known yet
+    fn emit_jump_to(
+        &mut self,
+        instruction: Instruction,
+        target: Address,
+        line: usize,
+    ) -> Result<(), AsmError> {
+        match target {
+            Address::Known(nnn) => {
+                let word = instruction.encode() | self.short_address(nnn as usize, line)?;
+                self.rom.extend(word.to_be_bytes());
+            }
+            Address::Label(label) => {
+                self.fixups.push(Fixup {
+                    addr: self.here(),
+                    label,
+                    line,
+                    long: false,
+                });
+                self.emit(instruction);
+            }
+        }
+
+        Ok(())
+    }
+
+    // checks an address fits in a 12 bit operand
+    fn short_address(&self, target: usize, line: usize) -> Result<u16, AsmError> {
+        if target > MAX_ADDR as usize {
+            return self.error(
+                line,
+                format!(
+                    "address {target:#06X} doesn't fit in 12 bits, use `i := long` for addresses above 0x0FFF"
+                ),
+            );
+        }
+
+        Ok(target as u16)
+    }
+
+    // emits F000 NNNN, with a 16 bit address which may not be known yet
+    fn emit_long_index(&mut self, target: Address, line: usize) {
+        self.emit(Instruction::LongIndex);
+
+        match target {
+            Address::Known(nnnn) => self.rom.extend(nnnn.to_be_bytes()),
+            Address::Label(label) => {
+                self.fixups.push(Fixup {
+                    addr: self.here(),
+                    label,
+                    line,
+                    long: true,
+                });
+                self.rom.extend([0, 0]);
+            }
+        }
+    }
+
+    // overwrites the address operand of an already emitted instruction
+    fn patch(&mut self, addr: usize, target: usize, line: usize) -> Result<(), AsmError> {
+        let nnn = self.short_address(target, line)?;
+        let offset = addr - SCRIPT_ADDR;
+        let word = u16::from_be_bytes([self.rom[offset], self.rom[offset + 1]]);
+        let word = (word & 0xF000) | nnn;
+
+        self.rom[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<(), AsmError> {
+        while self.pos < self.tokens.len() {
+            self.statement()?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, AsmError> {
+        if let Some(flow) = self.flow.last() {
+            let (line, what) = match flow {
+                Flow::Loop { line, .. } => (*line, "`loop` without `again`"),
+                Flow::If { line, .. } | Flow::Else { line, .. } => (*line, "`begin` without `end`"),
+            };
+
+            return self.error(line, what);
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&target) = self.labels.get(&fixup.label) else {
+                let message = if fixup.label == "main" {
+                    "no `main` label".to_string()
+                } else {
+                    format!("undefined label `{}`", fixup.label)
+                };
+
+                return self.error(fixup.line, message);
+            };
+
+            if fixup.long {
+                let offset = fixup.addr - SCRIPT_ADDR;
+                self.rom[offset..offset + 2].copy_from_slice(&(target as u16).to_be_bytes());
+            } else {
+                self.patch(fixup.addr, target, fixup.line)?;
+            }
+        }
+
+        let capacity = XO_CHIP_MEMORY_SIZE - SCRIPT_ADDR;
+
+        if self.rom.len() > capacity {
+            let line = self.tokens.last().map_or(1, |token| token.line);
+            return self.error(
+                line,
+                format!(
+                    "program is {} bytes, only {capacity} fit in memory",
+                    self.rom.len()
+                ),
+            );
+        }
+
+        Ok(self.rom)
+    }
+
+    fn statement(&mut self) -> Result<(), AsmError> {
+        let token = self.next()?;
+        let line = token.line;
+
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+
+                if self.labels.insert(name.text.clone(), self.here()).is_some() {
+                    return self.error(line, format!("label `{}` is already defined", name.text));
+                }
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.number()?;
+
+                self.constants.insert(name.text, value);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.register()?;
+
+                self.aliases.insert(name.text, reg);
+            }
+            ":macro" => self.define_macro()?,
+            ":call" => {
+                let target = self.address()?;
+                self.emit_jump_to(Instruction::Call { nnn: 0 }, target, line)?;
+            }
+            ":byte" => {
+                let value = self.byte()?;
+                self.rom.push(value);
+            }
+            "clear" => self.emit(Instruction::ClearScreen),
+            "return" | ";" => self.emit(Instruction::Return),
+            "scroll-down" => {
+                let n = self.number_in(0, 15)? as u8;
+                self.emit(Instruction::ScrollDown { n });
+            }
+            "scroll-right" => self.emit(Instruction::ScrollRight),
+            "scroll-left" => self.emit(Instruction::ScrollLeft),
+            "exit" => self.emit(Instruction::Exit),
+            "lores" => self.emit(Instruction::Lores),
+            "hires" => self.emit(Instruction::Hires),
+            "saveflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::SaveFlags { x });
+            }
+            "loadflags" => {
+                let x = self.register()?;
+                self.emit(Instruction::LoadFlags { x });
+            }
+            "bcd" => {
+                let x = self.register()?;
+                self.emit(Instruction::Bcd { x });
+            }
+            "save" => {
+                let x = self.register()?;
+
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    self.emit(Instruction::SaveRange { x, y });
+                } else {
+                    self.emit(Instruction::Store { x });
+                }
+            }
+            "load" => {
+                let x = self.register()?;
+
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let y = self.register()?;
+                    self.emit(Instruction::LoadRange { x, y });
+                } else {
+                    self.emit(Instruction::Load { x });
+                }
+            }
+            "scroll-up" => {
+                let n = self.number_in(0, 15)? as u8;
+                self.emit(Instruction::ScrollUp { n });
+            }
+            "plane" => {
+                let n = self.number_in(0, 3)? as u8;
+                self.emit(Instruction::Plane { n });
+            }
+            "audio" => self.emit(Instruction::Audio),
+            "pitch" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(Instruction::Pitch { x });
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let n = self.number_in(0, 15)? as u8;
+
+                self.emit(Instruction::Draw { x, y, n });
+            }
+            "jump" => {
+                let target = self.address()?;
+                self.emit_jump_to(Instruction::Jump { nnn: 0 }, target, line)?;
+            }
+            "jump0" => {
+                let target = self.address()?;
+                self.emit_jump_to(Instruction::JumpOffset { nnn: 0 }, target, line)?;
+            }
+            "i" => self.index_statement()?,
+            "delay" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(Instruction::SetDelay { x });
+            }
+            "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()?;
+                self.emit(Instruction::SetSound { x });
+            }
+            "if" => {
+                let condition = self.condition()?;
+                let keyword = self.next()?;
+
+                match keyword.text.as_str() {
+                    "then" => {
+                        self.emit(condition.skip_if_false);
+                        self.statement()?;
+                    }
+                    "begin" => {
+                        self.emit(condition.skip_if_true);
+
+                        let jump = self.here();
+                        self.emit(Instruction::Jump { nnn: 0 });
+                        self.flow.push(Flow::If { jump, line });
+                    }
+                    other => {
+                        return self.error(
+                            keyword.line,
+                            format!("expected `then` or `begin`, found `{other}`"),
+                        )
+                    }
+                }
+            }
+            "else" => {
+                let Some(Flow::If { jump: if_jump, .. }) = self.flow.pop() else {
+                    return self.error(line, "`else` without `if ... begin`");
+                };
+
+                let jump = self.here();
+                self.emit(Instruction::Jump { nnn: 0 });
+                self.patch(if_jump, self.here(), line)?;
+                self.flow.push(Flow::Else { jump, line });
+            }
+            "end" => match self.flow.pop() {
+                Some(Flow::If { jump, .. } | Flow::Else { jump, .. }) => {
+                    self.patch(jump, self.here(), line)?;
+                }
+                _ => return self.error(line, "`end` without `if ... begin`"),
+            },
+            "loop" => self.flow.push(Flow::Loop {
+                start: self.here(),
+                breaks: Vec::new(),
+                line,
+            }),
+            "while" => {
+                let condition = self.condition()?;
+
+                // `while` can be inside `if` blocks, it leaves the innermost loop
+                let Some(loop_index) = self
+                    .flow
+                    .iter()
+                    .rposition(|flow| matches!(flow, Flow::Loop { .. }))
+                else {
+                    return self.error(line, "`while` outside of a loop");
+                };
+
+                self.emit(condition.skip_if_true);
+
+                let jump = self.here();
+                self.emit(Instruction::Jump { nnn: 0 });
+
+                if let Flow::Loop { breaks, .. } = &mut self.flow[loop_index] {
+                    breaks.push(jump);
+                }
+            }
+            "again" => {
+                let Some(Flow::Loop { start, breaks, .. }) = self.flow.pop() else {
+                    return self.error(line, "`again` without `loop`");
+                };
+
+                self.emit_jump_to(
+                    Instruction::Jump { nnn: 0 },
+                    Address::Known(start as u16),
+                    line,
+                )?;
+
+                for jump in breaks {
+                    self.patch(jump, self.here(), line)?;
+                }
+            }
+            text => {
+                if let Some(x) = self.lookup_register(text) {
+                    self.register_statement(x, line)?;
+                } else if self.macros.contains_key(text) {
+                    self.expand_macro(&token)?;
+                } else if let Some(value) = self.lookup_number(text) {
+                    if !(-128..=255).contains(&value) {
+                        return self.error(line, format!("{value} does not fit in a byte"));
+                    }
+
+                    self.rom.push(value as u8);
+                } else if is_name(text) {
+                    // any other name is a call to a label
+                    let target = self.label_address(text);
+                    self.emit_jump_to(Instruction::Call { nnn: 0 }, target, line)?;
+                } else {
+                    return self.error(line, format!("unexpected `{text}`"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // statements starting with `i`
+    fn index_statement(&mut self) -> Result<(), AsmError> {
+        let op = self.next()?;
+
+        match op.text.as_str() {
+            ":=" => {
+                if self.peek() == Some("hex") {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit(Instruction::FontChar { x });
+                } else if self.peek() == Some("bighex") {
+                    self.next()?;
+                    let x = self.register()?;
+                    self.emit(Instruction::BigFontChar { x });
+                } else if self.peek() == Some("long") {
+                    self.next()?;
+                    let target = self.address_up_to(MAX_LONG_ADDR)?;
+                    self.emit_long_index(target, op.line);
+                } else {
+                    let target = self.address()?;
+                    self.emit_jump_to(Instruction::SetIndex { nnn: 0 }, target, op.line)?;
+                }
+            }
+            "+=" => {
+                let x = self.register()?;
+                self.emit(Instruction::AddIndex { x });
+            }
+            other => return self.error(op.line, format!("unknown operator `{other}` for `i`")),
+        }
+
+        Ok(())
+    }
+
+    // statements starting with a register
+    fn register_statement(&mut self, x: u8, line: usize) -> Result<(), AsmError> {
+        let op = self.next()?;
+        let rhs = self.next()?;
+        let y = self.lookup_register(&rhs.text);
+
+        let instruction = match (op.text.as_str(), y) {
+            (":=", Some(y)) => Instruction::Set { x, y },
+            (":=", None) => match rhs.text.as_str() {
+                "delay" => Instruction::GetDelay { x },
+                "key" => Instruction::WaitKey { x },
+                "random" => {
+                    let nn = self.byte()?;
+                    Instruction::Random { x, nn }
+                }
+                _ => {
+                    let nn = self.byte_value(&rhs)?;
+                    Instruction::SetImm { x, nn }
+                }
+            },
+            ("+=", Some(y)) => Instruction::Add { x, y },
+            ("+=", None) => {
+                let nn = self.byte_value(&rhs)?;
+                Instruction::AddImm { x, nn }
+            }
+            ("-=", Some(y)) => Instruction::Sub { x, y },
+            ("-=", None) => {
+                let nn = self.byte_value(&rhs)?;
+                Instruction::AddImm {
+                    x,
+                    nn: nn.wrapping_neg(),
+                }
+            }
+            ("=-", Some(y)) => Instruction::SubReverse { x, y },
+            ("|=", Some(y)) => Instruction::Or { x, y },
+            ("&=", Some(y)) => Instruction::And { x, y },
+            ("^=", Some(y)) => Instruction::Xor { x, y },
+            (">>=", Some(y)) => Instruction::ShiftRight { x, y },
+            ("<<=", Some(y)) => Instruction::ShiftLeft { x, y },
+            (op, _) => {
+                return self.error(
+                    line,
+                    format!("unknown operation `v{x:x} {op} {}`", rhs.text),
+                )
+            }
+        };
+
+        self.emit(instruction);
+
+        Ok(())
+    }
+
+    fn condition(&mut self) -> Result<Condition, AsmError> {
+        let x = self.register()?;
+        let op = self.next()?;
+
+        let (skip_if_false, skip_if_true) = match op.text.as_str() {
+            "key" => (Instruction::SkipNotKey { x }, Instruction::SkipKey { x }),
+            "-key" => (Instruction::SkipKey { x }, Instruction::SkipNotKey { x }),
+            "==" | "!=" => {
+                let rhs = self.next()?;
+
+                let (eq, ne) = match self.lookup_register(&rhs.text) {
+                    Some(y) => (Instruction::SkipEq { x, y }, Instruction::SkipNe { x, y }),
+                    None => {
+                        let nn = self.byte_value(&rhs)?;
+                        (
+                            Instruction::SkipEqImm { x, nn },
+                            Instruction::SkipNeImm { x, nn },
+                        )
+                    }
+                };
+
+                if op.text == "==" {
+                    (ne, eq)
+                } else {
+                    (eq, ne)
+                }
+            }
+            other => return self.error(op.line, format!("unsupported comparison `{other}`")),
+        };
+
+        Ok(Condition {
+            skip_if_false,
+            skip_if_true,
+        })
+    }
+
+    fn define_macro(&mut self) -> Result<(), AsmError> {
+        let name = self.name()?;
+        let mut args = Vec::new();
+
+        loop {
+            let token = self.next()?;
+
+            if token.text == "{" {
+                break;
+            }
+
+            args.push(token.text);
+        }
+
+        let mut body = Vec::new();
+        let mut depth = 1;
+
+        loop {
+            let token = self.next()?;
+
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+
+            if depth == 0 {
+                break;
+            }
+
+            body.push(token);
+        }
+
+        self.macros.insert(name.text, Macro { args, body });
+
+        Ok(())
+    }
+
+    // replaces a macro invocation with the macro body in the token stream
+    fn expand_macro(&mut self, name: &Token) -> Result<(), AsmError> {
+        // the body of a macro used inside another macro is nested one deeper than its use
+        if name.depth >= MAX_MACRO_DEPTH {
+            return self.error(name.line, format!("macro `{}` expands forever", name.text));
+        }
+
+        let count = self.macros[&name.text].args.len();
+        let mut values = Vec::new();
+
+        for _ in 0..count {
+            values.push(self.next()?.text);
+        }
+
+        let mac = &self.macros[&name.text];
+        let body: Vec<Token> = mac
+            .body
+            .iter()
+            .map(|token| {
+                let text = match mac.args.iter().position(|arg| *arg == token.text) {
+                    Some(i) => values[i].clone(),
+                    None => token.text.clone(),
+                };
+
+                // errors inside the macro are reported where it was used
+                Token {
+                    text,
+                    line: name.line,
+                    depth: name.depth + 1,
+                }
+            })
+            .collect();
+
+        self.tokens.splice(self.pos..self.pos, body);
+
+        Ok(())
+    }
+
+    fn lookup_register(&self, text: &str) -> Option<u8> {
+        parse_register(text).or_else(|| self.aliases.get(text).copied())
+    }
+
+    fn lookup_number(&self, text: &str) -> Option<i32> {
+        parse_number(text).or_else(|| self.constants.get(text).copied())
+    }
+
+    fn label_address(&self, text: &str) -> Address {
+        match self.labels.get(text) {
+            Some(&addr) => Address::Known(addr as u16),
+            None => Address::Label(text.to_string()),
+        }
+    }
+
+    fn name(&mut self) -> Result<Token, AsmError> {
+        let token = self.next()?;
+
+        if !is_name(&token.text) {
+            return self.error(token.line, format!("`{}` is not a valid name", token.text));
+        }
+
+        Ok(token)
+    }
+
+    fn register(&mut self) -> Result<u8, AsmError> {
+        let token = self.next()?;
+
+        match self.lookup_register(&token.text) {
+            Some(reg) => Ok(reg),
+            None => self.error(
+                token.line,
+                format!("expected a register, found `{}`", token.text),
+            ),
+        }
+    }
+
+    fn number(&mut self) -> Result<i32, AsmError> {
+        let token = self.next()?;
+
+        match self.lookup_number(&token.text) {
+            Some(value) => Ok(value),
+            None => self.error(
+                token.line,
+                format!("expected a number, found `{}`", token.text),
+            ),
+        }
+    }
+
+    fn number_in(&mut self, min: i32, max: i32) -> Result<i32, AsmError> {
+        let token = self.next()?;
+
+        match self.lookup_number(&token.text) {
+            Some(value) if (min..=max).contains(&value) => Ok(value),
+            Some(value) => self.error(
+                token.line,
+                format!("{value} is not between {min} and {max}"),
+            ),
+            None => self.error(
+                token.line,
+                format!("expected a number, found `{}`", token.text),
+            ),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, AsmError> {
+        let token = self.next()?;
+        self.byte_value(&token)
+    }
+
+    // reads a byte from a token that has already been consumed, negative values wrap
+    fn byte_value(&self, token: &Token) -> Result<u8, AsmError> {
+        match self.lookup_number(&token.text) {
+            Some(value) if (-128..=255).contains(&value) => Ok(value as u8),
+            Some(value) => self.error(token.line, format!("{value} does not fit in a byte")),
+            None => self.error(
+                token.line,
+                format!("expected a number, found `{}`", token.text),
+            ),
+        }
+    }
+
+    // reads an address, which is a number, a constant, or a label
+    fn address(&mut self) -> Result<Address, AsmError> {
+        self.address_up_to(MAX_ADDR)
+    }
+
+    // reads an address that can be as high as `max`
+    fn address_up_to(&mut self, max: i32) -> Result<Address, AsmError> {
+        let token = self.next()?;
+
+        if let Some(value) = self.lookup_number(&token.text) {
+            if !(0..=max).contains(&value) {
+                return self.error(token.line, format!("{value} is not a valid address"));
+            }
+
+            return Ok(Address::Known(value as u16));
+        }
+
+        if !is_name(&token.text) {
+            return self.error(
+                token.line,
+                format!("expected an address, found `{}`", token.text),
+            );
+        }
+
+        Ok(self.label_address(&token.text))
+    }
+}
+
+// whether `text` can be used as the name of a label, constant, alias or macro
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+
+    let valid_start = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+    valid_start
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && parse_register(text).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::{disassemble, Line};
+
+    // assembles a program, splitting the ROM into words
+    fn words(source: &str) -> Vec<u16> {
+        assemble(source)
+            .unwrap()
+            .chunks(2)
+            .map(|word| u16::from_be_bytes([word[0], word[1]]))
+            .collect()
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        let err = assemble(source).unwrap_err();
+
+        (err.line, err.message)
+    }
+
+    #[test]
+    fn labels_and_forward_references() {
+        assert_eq!(
+            words(": main\n  v0 := 5\n  jump later\n: later\n  exit"),
+            [0x1202, 0x6005, 0x1206, 0x00FD]
+        );
+
+        // calls to labels defined earlier and later, by name or with :call
+        assert_eq!(
+            words(": sub return\n: main\n  sub\n  :call sub\n  other\n: other ;"),
+            [0x1204, 0x00EE, 0x2202, 0x2202, 0x220A, 0x00EE]
+        );
+    }
+
+    #[test]
+    fn constants_aliases_and_macros() {
+        let source = "
+:const SPEED 3
+:alias x v4
+:macro bump reg amount { reg += amount }
+: main
+  x := SPEED
+  bump x 2
+  bump v1 -1
+";
+
+        assert_eq!(words(source), [0x1202, 0x6403, 0x7402, 0x71FF]);
+    }
+
+    #[test]
+    fn loops() {
+        // the break out of the loop lands after `again`
+        assert_eq!(
+            words(": main\n  loop\n    v0 += 1\n    while v0 != 10\n  again"),
+            [0x1202, 0x7001, 0x400A, 0x120A, 0x1202]
+        );
+    }
+
+    #[test]
+    fn while_inside_if() {
+        let source = "
+: main
+  loop
+    v0 += 1
+    if v1 == 0 begin
+      while v0 != 3
+    end
+  again
+";
+
+        assert_eq!(
+            words(source),
+            [0x1202, 0x7001, 0x3100, 0x120C, 0x4003, 0x120E, 0x1202]
+        );
+    }
+
+    #[test]
+    fn if_then_and_if_else() {
+        let source = "
+: main
+  if v0 == 1 then v1 := 2
+  if v0 != 1 begin
+    v2 := 3
+  else
+    v2 := 4
+  end
+";
+
+        assert_eq!(
+            words(source),
+            [0x1202, 0x4001, 0x6102, 0x4001, 0x120E, 0x6203, 0x1210, 0x6204]
+        );
+    }
+
+    #[test]
+    fn errors_name_their_line() {
+        assert_eq!(error(": start exit"), (1, "no `main` label".into()));
+        assert_eq!(
+            error(": main\n  jump nowhere"),
+            (2, "undefined label `nowhere`".into())
+        );
+        assert_eq!(
+            error(": main\n  clear\n  scroll-down 16"),
+            (3, "16 is not between 0 and 15".into())
+        );
+        assert_eq!(
+            error(": main\n  plane\n  v0"),
+            (3, "expected a number, found `v0`".into())
+        );
+        assert_eq!(
+            error(": main\n  v0 := 256"),
+            (2, "256 does not fit in a byte".into())
+        );
+        assert_eq!(
+            error(": main\n  while v0 == 1"),
+            (2, "`while` outside of a loop".into())
+        );
+        assert_eq!(
+            error(": main\n  loop\n  v0 += 1"),
+            (2, "`loop` without `again`".into())
+        );
+        assert_eq!(
+            error(": main\n\n  again"),
+            (3, "`again` without `loop`".into())
+        );
+        assert_eq!(
+            error(": main\n: main"),
+            (2, "label `main` is already defined".into())
+        );
+        assert_eq!(
+            error(": main\n  i := hex"),
+            (2, "unexpected end of file".into())
+        );
+
+        // errors inside macros are reported where the macro is used
+        assert_eq!(
+            error(":macro big { v0 := 300 }\n: main\n  big"),
+            (3, "300 does not fit in a byte".into())
+        );
+
+        // only nesting is limited, so a macro can be used any number of times
+        let uses = "nop\n".repeat(20_000);
+        assert!(assemble(&format!(":macro nop {{ v0 += 0 }}\n: main\n{uses}")).is_ok());
+        assert_eq!(
+            error(":macro a { b }\n:macro b { a }\n: main\n  a"),
+            (4, "macro `a` expands forever".into())
+        );
+    }
+
+    #[test]
+    fn round_trips_through_the_disassembler() {
+        let source = "
+: main
+  clear
+  hires
+  v0 := 1
+  v1 := v0
+  v1 += v0
+  v1 -= v0
+  v1 =- v0
+  v2 |= v1
+  v2 &= v1
+  v2 ^= v1
+  v3 >>= v2
+  v3 <<= v2
+  v4 := random 0x0F
+  v5 := delay
+  v6 := key
+  delay := v6
+  buzzer := v6
+  if v0 key then v0 := 2
+  if v0 -key then v0 := 3
+  if v0 == v1 then v0 := 4
+  i := long 0x1234
+  i += v0
+  i := hex v0
+  i := bighex v0
+  bcd v0
+  save v3
+  load v3
+  save v1 - v3
+  load v1 - v3
+  saveflags v2
+  loadflags v2
+  sprite v0 v1 5
+  scroll-down 2
+  scroll-up 3
+  scroll-left
+  scroll-right
+  plane 3
+  audio
+  pitch := v0
+  lores
+  exit
+";
+
+        let rom = assemble(source).unwrap();
+        let disassembly = disassemble(&rom);
+        let mut reassembled = Vec::new();
+
+        for line in &disassembly.lines {
+            match *line {
+                Line::Code {
+                    opcode,
+                    instruction,
+                    ..
+                } => {
+                    assert_eq!(instruction.encode(), opcode, "{instruction}");
+                    reassembled.extend(opcode.to_be_bytes());
+                }
+                Line::LongIndex { nnnn, .. } => {
+                    reassembled.extend(Instruction::LongIndex.encode().to_be_bytes());
+                    reassembled.extend(nnnn.to_be_bytes());
+                }
+                Line::Data { addr, .. } => panic!("0x{addr:03X} was disassembled as data"),
+            }
+        }
+
+        assert_eq!(reassembled, rom);
+    }
+
+    // assembles a program with `padding` zero bytes between `main` and the label `far`
+    fn with_far_label(code: &str, padding: usize) -> Result<Vec<u8>, AsmError> {
+        let padding = vec!["0"; padding].join(" ");
+
+        assemble(&format!(": main\n{code}\n{padding}\n: far 0"))
+    }
+
+    #[test]
+    fn addresses_past_12_bits_are_rejected() {
+        // `far` ends up at 0x11A6, after the jump to main, one instruction and the padding
+        let padding = 0x11A6 - 0x204;
+
+        for code in ["i := far", "jump far", "jump0 far", ":call far", "far"] {
+            let err = with_far_label(code, padding).unwrap_err();
+
+            assert_eq!(err.line, 2, "{code}");
+            assert!(err.message.contains("i := long"), "{code}: {}", err.message);
+        }
+    }
+
+    #[test]
+    fn addresses_past_12_bits_are_rejected_once_known() {
+        let padding = vec!["0"; 0x1000].join(" ");
+        let err = assemble(&format!(": main\n{padding}\n: far\njump far")).unwrap_err();
+
+        assert_eq!(err.line, 4);
+    }
+
+    #[test]
+    fn long_index_reaches_past_12_bits() {
+        let rom = with_far_label("i := long far", 0x11A6 - 0x206).unwrap();
+
+        assert_eq!(rom[2..6], [0xF0, 0x00, 0x11, 0xA6]);
+    }
+}
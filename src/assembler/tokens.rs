@@ -0,0 +1,65 @@
+/// A single whitespace separated word of source, with the line it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    pub line: usize,  // 1 based line number
+    pub depth: usize, // how many macro expansions it is nested in, 0 if it was written in the source
+}
+
+/// Splits source into tokens, dropping `#` comments
+///
+/// # Arguments
+///
+/// * `source` - Octo source code
+pub fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        // everything after a # is a comment
+        let code = match line.find('#') {
+            Some(start) => &line[..start],
+            None => line,
+        };
+
+        for word in code.split_whitespace() {
+            tokens.push(Token {
+                text: word.to_string(),
+                line: i + 1,
+                depth: 0,
+            });
+        }
+    }
+
+    tokens
+}
+
+/// Parses a number literal in decimal, hex (`0x`) or binary (`0b`), optionally negated
+pub fn parse_number(text: &str) -> Option<i32> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i32::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i32::from_str_radix(bin, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+/// Parses a register name, `v0` through `vf`
+pub fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+
+    if digit.len() != 1 {
+        return None;
+    }
+
+    u8::from_str_radix(digit, 16).ok()
+}
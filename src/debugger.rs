@@ -0,0 +1,554 @@
+//! Interactive debugger for running a ROM one instruction at a time
+//!
+//! [`Debugger`] wraps an [`Emulator`] with breakpoints and ways of running
+//! until something interesting happens, [`Debugger::repl`] drives it from
+//! text commands such as `break 20A`, `step` and `regs`, and [`gdb`] lets
+//! GDB drive it over TCP
+
+pub mod gdb;
+mod watch;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+pub use watch::{Compare, Condition, Operand, Target, Watch};
+
+use crate::emulator::{decode, Access, Chip8Error, Emulator, Instruction};
+use crate::frontend::parse_keys;
+
+const HELP: &str = "commands:
+    break [addr]        set a breakpoint, or list them without an address
+    delete <addr>       remove a breakpoint
+    watch <target>      stop when a register, I or memory changes, target is vX, i or addr [len]
+    rwatch <addr> [len] stop when memory is read
+    awatch <addr> [len] stop when memory is read or written
+                        any watch can end with a condition, such as `if v3 == 10`
+    watch               list watches
+    unwatch <n>         remove a watch
+    step [count]        execute one instruction, or count instructions
+    next                like step, but runs called subroutines to completion
+    continue            run until a breakpoint is reached, or ctrl-c is pressed
+    finish              run until the current subroutine returns
+    regs                show the registers, index, program counter and timers
+    mem <addr> [len]    show len bytes of memory starting at addr (default 16)
+    stack               show the return addresses on the call stack
+    disasm [addr] [n]   show n instructions starting at addr (default the program counter)
+    key [keys]          hold keys such as `5a`, `-` for none, or show the held keys
+    quit                stop debugging
+addresses and values are in hex, counts and lengths are in decimal
+an empty line repeats the last command";
+
+/// Why the debugger stopped running the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Done,                         // the requested instructions were executed
+    Breakpoint(usize),            // the program counter reached a breakpoint
+    Watch { id: u32, pc: usize }, // a watch was triggered by the instruction at pc
+    Halted,                       // the program exited with 00FD
+    Interrupted,                  // Debugger::interrupt was set while running
+    Error(Chip8Error),            // an instruction failed, the emulator is halted
+}
+
+/// An emulator that can be paused, stepped and inspected
+pub struct Debugger {
+    pub emu: Emulator,                // the machine being debugged
+    pub breakpoints: BTreeSet<usize>, // addresses to stop at before executing
+    watches: BTreeMap<u32, Watch>,    // watches by the number they are shown with
+    next_watch: u32,                  // number given to the next watch
+    cycles: u32,                      // instructions executed since the timers last ticked
+    pub interrupt: Arc<AtomicBool>, // set from elsewhere, such as a ctrl-c handler, to stop running
+}
+
+impl Debugger {
+    /// Wraps an emulator that already has its font and script loaded
+    pub fn new(emu: Emulator) -> Self {
+        Self {
+            emu,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeMap::new(),
+            next_watch: 1,
+            cycles: 0,
+            interrupt: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Adds a watch, returning the number it is shown with
+    pub fn add_watch(&mut self, watch: Watch) -> u32 {
+        let id = self.next_watch;
+
+        self.watches.insert(id, watch);
+        self.next_watch += 1;
+        self.sync_watchpoints();
+
+        id
+    }
+
+    /// Removes a watch by number, returning it if it existed
+    pub fn remove_watch(&mut self, id: u32) -> Option<Watch> {
+        let watch = self.watches.remove(&id);
+
+        self.sync_watchpoints();
+
+        watch
+    }
+
+    /// Iterates over the watches and their numbers
+    pub fn watches(&self) -> impl Iterator<Item = (u32, &Watch)> {
+        self.watches.iter().map(|(id, watch)| (*id, watch))
+    }
+
+    // makes the emulator record accesses to every watched range of memory
+    fn sync_watchpoints(&mut self) {
+        self.emu.watchpoints = self
+            .watches
+            .values()
+            .filter_map(|watch| match watch.target {
+                Target::Memory(watchpoint) => Some(watchpoint),
+                _ => None,
+            })
+            .collect();
+    }
+
+    // finds the first watch triggered by the last instruction, given the registers and
+    // index from before it
+    fn triggered_watch(&self, reg: [u8; 16], index: usize) -> Option<u32> {
+        let emu = &self.emu;
+
+        self.watches
+            .iter()
+            .find(|(_, watch)| {
+                let triggered = match watch.target {
+                    Target::Memory(watchpoint) => emu
+                        .watch_hits
+                        .iter()
+                        .any(|hit| hit.watchpoint == watchpoint),
+                    Target::Reg(x) => emu.reg[x as usize] != reg[x as usize],
+                    Target::Index => emu.index != index,
+                };
+
+                triggered && watch.condition.is_none_or(|condition| condition.holds(emu))
+            })
+            .map(|(id, _)| *id)
+    }
+
+    /// Executes one instruction, ticking the timers every frame's worth of instructions
+    ///
+    /// Stops with [`Stop::Watch`] if the instruction triggered a watch
+    pub fn step(&mut self) -> Stop {
+        if self.emu.halted {
+            return Stop::Halted;
+        }
+
+        let (reg, index, pc) = (self.emu.reg, self.emu.index, self.emu.counter);
+        self.emu.watch_hits.clear();
+
+        if let Err(err) = self.emu.step() {
+            return Stop::Error(err);
+        }
+
+        self.cycles += 1;
+
+        if self.cycles >= self.emu.cycles_per_frame() {
+            self.emu.tick_timers();
+            self.cycles = 0;
+        }
+
+        if let Some(id) = self.triggered_watch(reg, index) {
+            return Stop::Watch { id, pc };
+        }
+
+        if self.emu.halted {
+            Stop::Halted
+        } else {
+            Stop::Done
+        }
+    }
+
+    /// Executes instructions until `done` returns true, a breakpoint is reached or
+    /// [`Debugger::interrupt`] is set
+    ///
+    /// At least one instruction is always executed, so this can be used to
+    /// move on from a breakpoint
+    pub fn run_until(&mut self, mut done: impl FnMut(&Emulator) -> bool) -> Stop {
+        loop {
+            let stop = self.step();
+
+            if stop != Stop::Done {
+                return stop;
+            }
+
+            if self.interrupt.swap(false, Ordering::Relaxed) {
+                return Stop::Interrupted;
+            }
+
+            // breakpoints are checked before `done`, otherwise one reached by the last
+            // instruction of a run would be stepped past by the next run
+            if self.breakpoints.contains(&self.emu.counter) {
+                return Stop::Breakpoint(self.emu.counter);
+            }
+
+            if done(&self.emu) {
+                return stop;
+            }
+        }
+    }
+
+    /// Runs until a breakpoint is reached or the program stops
+    pub fn resume(&mut self) -> Stop {
+        self.run_until(|_| false)
+    }
+
+    /// Executes one instruction, running a called subroutine until it returns
+    pub fn step_over(&mut self) -> Stop {
+        let Some(Ok(Instruction::Call { .. })) = self.emu.peek_word(self.emu.counter).map(decode)
+        else {
+            return self.step();
+        };
+
+        let (target, depth) = (self.emu.counter + 2, self.emu.stack.len());
+
+        self.run_until(|emu| emu.counter == target && emu.stack.len() == depth)
+    }
+
+    /// Runs until the current subroutine returns, or returns `None` outside of a subroutine
+    pub fn finish(&mut self) -> Option<Stop> {
+        let depth = self.emu.stack.len();
+
+        if depth == 0 {
+            return None;
+        }
+
+        Some(self.run_until(|emu| emu.stack.len() < depth))
+    }
+
+    /// Reads commands until `quit` or the end of the input, writing results to `out`
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Where commands are read from, one per line
+    /// * `out` - Where prompts and results are written
+    pub fn repl(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        let mut last = String::new();
+        let mut lines = input.lines();
+
+        self.write_location(&mut out)?;
+
+        loop {
+            write!(out, "(chip8) ")?;
+            out.flush()?;
+
+            let Some(line) = lines.next() else {
+                return Ok(());
+            };
+
+            let line = line?;
+            let line = line.trim();
+
+            // an empty line repeats the last command
+            if !line.is_empty() {
+                last = line.to_string();
+            }
+
+            // an interrupt while waiting for a command shouldn't stop the next one
+            self.interrupt.store(false, Ordering::Relaxed);
+
+            if !self.command(&last, &mut out)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Runs a single command, returning false once the debugger should quit
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The command and its arguments, separated by whitespace
+    /// * `out` - Where the results are written
+    pub fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            return Ok(true);
+        };
+        let args: Vec<&str> = words.collect();
+
+        let result = match name {
+            "break" | "b" => self.cmd_break(&args, out),
+            "delete" | "d" => self.cmd_delete(&args, out),
+            "watch" | "w" if args.is_empty() => self.cmd_watches(out),
+            "watch" | "w" => self.cmd_watch(Access::Write, &args, out),
+            "rwatch" => self.cmd_watch(Access::Read, &args, out),
+            "awatch" => self.cmd_watch(Access::Any, &args, out),
+            "unwatch" => self.cmd_unwatch(&args, out),
+            "step" | "s" => self.cmd_step(&args, out),
+            "next" | "n" => {
+                let stop = self.step_over();
+                self.report(stop, out)
+            }
+            "continue" | "c" => {
+                let stop = self.resume();
+                self.report(stop, out)
+            }
+            "finish" | "f" => match self.finish() {
+                Some(stop) => self.report(stop, out),
+                None => writeln!(out, "not in a subroutine"),
+            },
+            "regs" | "r" => self.cmd_regs(out),
+            "mem" | "m" => self.cmd_mem(&args, out),
+            "stack" => self.cmd_stack(out),
+            "disasm" | "x" => self.cmd_disasm(&args, out),
+            "key" | "k" => self.cmd_key(&args, out),
+            "help" | "h" => writeln!(out, "{HELP}"),
+            "quit" | "q" => return Ok(false),
+            _ => writeln!(out, "unknown command `{name}`, try `help`"),
+        };
+
+        result.map(|_| true)
+    }
+
+    fn cmd_break(&mut self, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        let Some(arg) = args.first() else {
+            for addr in &self.breakpoints {
+                writeln!(out, "0x{addr:03X}")?;
+            }
+
+            return Ok(());
+        };
+
+        match parse_hex(arg) {
+            Some(addr) => {
+                self.breakpoints.insert(addr);
+                writeln!(out, "breakpoint at 0x{addr:03X}")
+            }
+            None => writeln!(out, "invalid address `{arg}`"),
+        }
+    }
+
+    fn cmd_delete(&mut self, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        match args.first().map(|arg| (arg, parse_hex(arg))) {
+            Some((_, Some(addr))) if self.breakpoints.remove(&addr) => Ok(()),
+            Some((_, Some(addr))) => writeln!(out, "no breakpoint at 0x{addr:03X}"),
+            Some((arg, None)) => writeln!(out, "invalid address `{arg}`"),
+            None => writeln!(out, "delete needs an address"),
+        }
+    }
+
+    fn cmd_watch(&mut self, access: Access, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        match Watch::parse(access, args, self.emu.memory.len()) {
+            Ok(watch) => {
+                let id = self.add_watch(watch);
+                writeln!(out, "#{id}  {watch}")
+            }
+            Err(err) => writeln!(out, "{err}"),
+        }
+    }
+
+    fn cmd_watches(&self, out: &mut impl Write) -> io::Result<()> {
+        for (id, watch) in self.watches() {
+            writeln!(out, "#{id}  {watch}")?;
+        }
+
+        Ok(())
+    }
+
+    fn cmd_unwatch(&mut self, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        let Some(arg) = args.first() else {
+            return writeln!(out, "unwatch needs a watch number");
+        };
+
+        match arg.trim_start_matches('#').parse() {
+            Ok(id) if self.remove_watch(id).is_some() => Ok(()),
+            _ => writeln!(out, "no watch `{arg}`"),
+        }
+    }
+
+    fn cmd_step(&mut self, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        let count = match args.first() {
+            Some(arg) => match arg.parse::<usize>() {
+                Ok(count) => count,
+                Err(_) => return writeln!(out, "invalid count `{arg}`"),
+            },
+            None => 1,
+        };
+
+        let mut remaining = count;
+        let stop = self.run_until(|_| {
+            remaining = remaining.saturating_sub(1);
+            remaining == 0
+        });
+
+        self.report(stop, out)
+    }
+
+    fn cmd_regs(&self, out: &mut impl Write) -> io::Result<()> {
+        let emu = &self.emu;
+
+        for (half, regs) in emu.reg.chunks(8).enumerate() {
+            let line: Vec<String> = regs
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X}={value:02X}", half * 8 + i))
+                .collect();
+
+            writeln!(out, "{}", line.join(" "))?;
+        }
+
+        writeln!(
+            out,
+            "I={:04X} PC={:04X} DT={:02X} ST={:02X}",
+            emu.index, emu.counter, emu.timer, emu.s_timer
+        )
+    }
+
+    fn cmd_mem(&self, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        let Some(addr) = args.first().and_then(|arg| parse_hex(arg)) else {
+            return writeln!(out, "mem needs an address");
+        };
+
+        let len = match args.get(1).map(|arg| arg.parse::<usize>()) {
+            Some(Ok(len)) => len,
+            Some(Err(_)) => return writeln!(out, "invalid length `{}`", args[1]),
+            None => 16,
+        };
+
+        // show whatever part of the range is actually in memory
+        let end = addr.saturating_add(len).min(self.emu.memory.len());
+        let bytes = self.emu.memory.get(addr..end).unwrap_or_default();
+
+        for (row, chunk) in bytes.chunks(16).enumerate() {
+            write!(out, "0x{:03X} ", addr + row * 16)?;
+
+            for byte in chunk {
+                write!(out, " {byte:02X}")?;
+            }
+
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+
+    fn cmd_stack(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.emu.stack.is_empty() {
+            return writeln!(out, "stack is empty");
+        }
+
+        for (depth, addr) in self.emu.call_stack().enumerate() {
+            writeln!(out, "#{depth}  0x{addr:03X}")?;
+        }
+
+        Ok(())
+    }
+
+    fn cmd_disasm(&self, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        let mut addr = match args.first() {
+            Some(arg) => match parse_hex(arg) {
+                Some(addr) => addr,
+                None => return writeln!(out, "invalid address `{arg}`"),
+            },
+            None => self.emu.counter,
+        };
+
+        let count = match args.get(1).map(|arg| arg.parse::<usize>()) {
+            Some(Ok(count)) => count,
+            Some(Err(_)) => return writeln!(out, "invalid count `{}`", args[1]),
+            None => 10,
+        };
+
+        for _ in 0..count {
+            match self.write_instruction(addr, out)? {
+                Some(len) => addr += len,
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    // holds keys until the next `key` command, there's no keypad while debugging
+    fn cmd_key(&mut self, args: &[&str], out: &mut impl Write) -> io::Result<()> {
+        let Some(arg) = args.first() else {
+            let held: String = (0..16)
+                .filter(|&key| self.emu.keys[key])
+                .map(|key| format!("{key:X}"))
+                .collect();
+
+            if held.is_empty() {
+                return writeln!(out, "no keys held");
+            }
+
+            return writeln!(out, "holding {held}");
+        };
+
+        match parse_keys(arg) {
+            Some(keys) => {
+                self.emu.keys = keys;
+                Ok(())
+            }
+            None => writeln!(out, "invalid keys `{arg}`"),
+        }
+    }
+
+    // writes why the program stopped, followed by where it is now
+    fn report(&self, stop: Stop, out: &mut impl Write) -> io::Result<()> {
+        match stop {
+            Stop::Done => {}
+            Stop::Breakpoint(addr) => writeln!(out, "breakpoint at 0x{addr:03X}")?,
+            Stop::Watch { id, pc } => {
+                writeln!(out, "#{id}  {} triggered by 0x{pc:03X}", self.watches[&id])?
+            }
+            Stop::Halted => writeln!(out, "program halted")?,
+            Stop::Interrupted => writeln!(out, "interrupted")?,
+            Stop::Error(err) => writeln!(out, "error: {err}")?,
+        }
+
+        self.write_location(out)
+    }
+
+    // writes the instruction at the program counter
+    fn write_location(&self, out: &mut impl Write) -> io::Result<()> {
+        self.write_instruction(self.emu.counter, out).map(|_| ())
+    }
+
+    // writes the instruction at `addr` in the same format as the disassembler,
+    // returning its length or None if it is past the end of memory
+    fn write_instruction(&self, addr: usize, out: &mut impl Write) -> io::Result<Option<usize>> {
+        let Some(opcode) = self.emu.peek_word(addr) else {
+            return Ok(None);
+        };
+
+        // the program counter and breakpoints are marked in the margin
+        let marker = match (addr == self.emu.counter, self.breakpoints.contains(&addr)) {
+            (true, _) => '>',
+            (false, true) => '*',
+            (false, false) => ' ',
+        };
+
+        write!(out, "{marker} 0x{addr:03X}  ")?;
+
+        match decode(opcode) {
+            Ok(Instruction::LongIndex) => match self.emu.peek_word(addr + 2) {
+                Some(nnnn) => {
+                    writeln!(out, "F000 {nnnn:04X}  LD I, 0x{nnnn:04X}")?;
+                    return Ok(Some(4));
+                }
+                None => writeln!(out, "{opcode:04X}  LD I, LONG")?,
+            },
+            Ok(instruction) => writeln!(out, "{opcode:04X}  {instruction}")?,
+            Err(_) => writeln!(out, "{opcode:04X}  ???")?,
+        }
+
+        Ok(Some(2))
+    }
+}
+
+// parses an address written in hex, with or without a 0x prefix
+fn parse_hex(text: &str) -> Option<usize> {
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+
+    usize::from_str_radix(digits, 16).ok()
+}
@@ -0,0 +1,485 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::{Debugger, Stop, Target, Watch};
+use crate::emulator::{Access, Chip8Error, Watchpoint};
+
+// describes the registers to GDB, in the order they appear in `g` packets
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8" type="uint8"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+// register numbers after V0 to VF
+const REG_I: usize = 16;
+const REG_PC: usize = 17;
+const REG_SP: usize = 18;
+const REG_DT: usize = 19;
+const REG_ST: usize = 20;
+const REG_COUNT: usize = 21;
+
+// how many instructions run between checks for an interrupt from GDB while continuing
+const POLL_CYCLES: u32 = 4096;
+
+// the byte GDB sends to interrupt a running program
+const INTERRUPT: u8 = 0x03;
+
+/// Serves the GDB remote serial protocol for one connection
+///
+/// Registers are numbered V0 to VF, then I, PC, SP (the stack depth), DT and
+/// ST. I and PC are two bytes wide, everything else is one byte, and values
+/// are sent little endian as GDB expects
+pub struct GdbStub<'a> {
+    debugger: &'a mut Debugger, // the program being debugged
+    stream: TcpStream,          // connection to GDB
+}
+
+/// Waits for GDB to connect on `addr`, then serves it until it detaches
+///
+/// # Arguments
+///
+/// * `debugger` - The program to debug
+/// * `addr` - Address to listen on, such as `127.0.0.1:1234`
+pub fn listen(debugger: &mut Debugger, addr: impl ToSocketAddrs) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let (stream, _) = listener.accept()?;
+
+    GdbStub::new(debugger, stream).serve()
+}
+
+impl<'a> GdbStub<'a> {
+    pub fn new(debugger: &'a mut Debugger, stream: TcpStream) -> Self {
+        Self { debugger, stream }
+    }
+
+    /// Answers packets until GDB detaches, kills the program or disconnects
+    pub fn serve(&mut self) -> io::Result<()> {
+        // packets are tiny and every one waits for a reply, so don't let them be held back
+        self.stream.set_nodelay(true)?;
+
+        while let Some(packet) = self.read_packet()? {
+            let packet = String::from_utf8_lossy(&packet).into_owned();
+
+            match self.handle(&packet)? {
+                Some(reply) => self.write_packet(&reply)?,
+                None => return Ok(()),
+            }
+        }
+
+        Ok(())
+    }
+
+    // answers one packet, or returns None when the session is over
+    fn handle(&mut self, packet: &str) -> io::Result<Option<String>> {
+        // packets come straight from the network, so the first byte may not be a whole character
+        let Some((kind, body)) = packet.split_at_checked(packet.len().min(1)) else {
+            return Ok(Some("E01".to_string()));
+        };
+
+        let reply = match kind {
+            "?" => "S05".to_string(),
+            "g" => self.read_registers(),
+            "G" => ok_or_error(self.write_registers(body)),
+            "p" => match usize::from_str_radix(body, 16)
+                .ok()
+                .and_then(|n| self.read_register(n))
+            {
+                Some(value) => value,
+                None => "E01".to_string(),
+            },
+            "P" => ok_or_error(self.write_register(body)),
+            "m" => self.read_memory(body),
+            "M" => ok_or_error(self.write_memory(body)),
+            "Z" => ok_or_error(self.breakpoint(body, true)),
+            "z" => ok_or_error(self.breakpoint(body, false)),
+            "s" | "c" => {
+                if let Some(addr) = parse_hex(body) {
+                    self.debugger.emu.counter = addr;
+                }
+
+                if kind == "s" {
+                    let stop = self.debugger.step();
+                    self.stop_reply(stop)
+                } else {
+                    let stop = self.resume()?;
+                    self.stop_reply(stop)
+                }
+            }
+            "H" => "OK".to_string(),
+            "k" => return Ok(None),
+            "D" => {
+                self.write_packet("OK")?;
+                return Ok(None);
+            }
+            "q" => self.query(body),
+            _ => String::new(),
+        };
+
+        Ok(Some(reply))
+    }
+
+    // answers general queries, anything unknown gets the empty unsupported reply
+    fn query(&self, body: &str) -> String {
+        if body.starts_with("Supported") {
+            return "PacketSize=4000;qXfer:features:read+".to_string();
+        }
+
+        if let Some(args) = body.strip_prefix("Xfer:features:read:target.xml:") {
+            return match parse_pair(args, ',') {
+                Some((offset, len)) => {
+                    let xml = TARGET_XML.as_bytes();
+                    let start = offset.min(xml.len());
+                    let end = start.saturating_add(len).min(xml.len());
+                    let more = if end < xml.len() { 'm' } else { 'l' };
+
+                    format!("{more}{}", String::from_utf8_lossy(&xml[start..end]))
+                }
+                None => "E01".to_string(),
+            };
+        }
+
+        match body {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // continues until something stops the program or GDB interrupts it
+    fn resume(&mut self) -> io::Result<Stop> {
+        loop {
+            let mut cycles = 0;
+            let stop = self.debugger.run_until(|_| {
+                cycles += 1;
+                cycles == POLL_CYCLES
+            });
+
+            if stop != Stop::Done {
+                return Ok(stop);
+            }
+
+            if self.interrupted()? {
+                return Ok(Stop::Interrupted);
+            }
+        }
+    }
+
+    // checks for the interrupt byte without waiting, or for GDB disconnecting
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let mut byte = [0];
+
+        self.stream.set_nonblocking(true)?;
+        let read = self.stream.read(&mut byte);
+        self.stream.set_nonblocking(false)?;
+
+        match read {
+            Ok(1) => Ok(byte[0] == INTERRUPT),
+            // GDB went away, stop so the next read notices
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    // describes why the program stopped
+    fn stop_reply(&self, stop: Stop) -> String {
+        match stop {
+            Stop::Done | Stop::Breakpoint(_) => "S05".to_string(),
+            Stop::Watch { id, .. } => match self.debugger.watches.get(&id).map(|w| w.target) {
+                Some(Target::Memory(watchpoint)) => {
+                    let kind = match watchpoint.access {
+                        Access::Read => "rwatch",
+                        Access::Write => "watch",
+                        Access::Any => "awatch",
+                    };
+
+                    format!("T05{kind}:{:x};", watchpoint.addr)
+                }
+                _ => "S05".to_string(),
+            },
+            Stop::Halted => "W00".to_string(),
+            Stop::Interrupted => "S02".to_string(),
+            // SIGILL for bad instructions, SIGSEGV for everything else
+            Stop::Error(Chip8Error::UnknownOpcode { .. }) => "S04".to_string(),
+            Stop::Error(_) => "S0b".to_string(),
+        }
+    }
+
+    fn read_registers(&self) -> String {
+        (0..REG_COUNT)
+            .filter_map(|n| self.read_register(n))
+            .collect()
+    }
+
+    fn read_register(&self, n: usize) -> Option<String> {
+        let emu = &self.debugger.emu;
+
+        let bytes = match n {
+            0..=15 => vec![emu.reg[n]],
+            REG_I => (emu.index as u16).to_le_bytes().to_vec(),
+            REG_PC => (emu.counter as u16).to_le_bytes().to_vec(),
+            REG_SP => vec![emu.stack.len() as u8],
+            REG_DT => vec![emu.timer],
+            REG_ST => vec![emu.s_timer],
+            _ => return None,
+        };
+
+        Some(to_hex(&bytes))
+    }
+
+    fn write_registers(&mut self, body: &str) -> Option<()> {
+        let bytes = from_hex(body)?;
+        let mut at = 0;
+
+        for n in 0..REG_COUNT {
+            let width = register_width(n);
+            let value = bytes.get(at..at + width)?;
+
+            self.set_register(n, value)?;
+            at += width;
+        }
+
+        Some(())
+    }
+
+    fn write_register(&mut self, body: &str) -> Option<()> {
+        let (n, value) = body.split_once('=')?;
+        let n = usize::from_str_radix(n, 16).ok()?;
+        let value = from_hex(value)?;
+
+        if value.len() != register_width(n) {
+            return None;
+        }
+
+        self.set_register(n, &value)
+    }
+
+    // sets a register from its little endian bytes
+    fn set_register(&mut self, n: usize, value: &[u8]) -> Option<()> {
+        let emu = &mut self.debugger.emu;
+        let wide = || u16::from_le_bytes([value[0], value[1]]) as usize;
+
+        match n {
+            0..=15 => emu.reg[n] = value[0],
+            REG_I => emu.index = wide(),
+            REG_PC => emu.counter = wide(),
+            // the stack can only be made shallower, there is nothing to fill it with
+            REG_SP => {
+                let depth = value[0] as usize;
+
+                if depth > emu.stack.len() {
+                    return None;
+                }
+
+                emu.stack.truncate(depth);
+            }
+            REG_DT => emu.timer = value[0],
+            REG_ST => emu.s_timer = value[0],
+            _ => return None,
+        }
+
+        Some(())
+    }
+
+    fn read_memory(&self, body: &str) -> String {
+        let bytes = parse_pair(body, ',').and_then(|(addr, len)| self.debugger.emu.peek(addr, len));
+
+        match bytes {
+            Some(bytes) => to_hex(bytes),
+            None => "E01".to_string(),
+        }
+    }
+
+    fn write_memory(&mut self, body: &str) -> Option<()> {
+        let (range, data) = body.split_once(':')?;
+        let (addr, len) = parse_pair(range, ',')?;
+        let data = from_hex(data)?;
+
+        if data.len() != len {
+            return None;
+        }
+
+        // addresses come straight from GDB, so they can be anything
+        self.debugger
+            .emu
+            .memory
+            .get_mut(addr..addr.checked_add(len)?)?
+            .copy_from_slice(&data);
+
+        Some(())
+    }
+
+    // handles Z and z packets, breakpoints are types 0 and 1 and watchpoints 2 to 4
+    fn breakpoint(&mut self, body: &str, insert: bool) -> Option<()> {
+        let mut fields = body.split(',');
+        let kind = fields.next()?;
+        let addr = parse_hex(fields.next()?)?;
+        let len = parse_hex(fields.next()?)?;
+
+        let access = match kind {
+            "0" | "1" => {
+                if insert {
+                    self.debugger.breakpoints.insert(addr);
+                } else {
+                    self.debugger.breakpoints.remove(&addr);
+                }
+
+                return Some(());
+            }
+            "2" => Access::Write,
+            "3" => Access::Read,
+            "4" => Access::Any,
+            _ => return None,
+        };
+
+        let target = Target::Memory(Watchpoint { addr, len, access });
+
+        if insert {
+            // the range comes straight from GDB, so make sure it is actually in memory
+            addr.checked_add(len)
+                .filter(|&end| end <= self.debugger.emu.memory.len())?;
+
+            self.debugger.add_watch(Watch {
+                target,
+                condition: None,
+            });
+        } else {
+            let id = self
+                .debugger
+                .watches()
+                .find(|(_, watch)| watch.target == target)
+                .map(|(id, _)| id)?;
+
+            self.debugger.remove_watch(id);
+        }
+
+        Some(())
+    }
+
+    // reads the next packet, acknowledging it, or returns None once GDB disconnects
+    fn read_packet(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // skip acknowledgements and anything else between packets
+            loop {
+                match self.read_byte()? {
+                    Some(b'$') => break,
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            }
+
+            let mut data = Vec::new();
+
+            loop {
+                match self.read_byte()? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+
+            let (Some(high), Some(low)) = (self.read_byte()?, self.read_byte()?) else {
+                return Ok(None);
+            };
+
+            let sent = std::str::from_utf8(&[high, low])
+                .ok()
+                .and_then(|text| u8::from_str_radix(text, 16).ok());
+
+            if sent == Some(checksum(&data)) {
+                self.stream.write_all(b"+")?;
+                return Ok(Some(data));
+            }
+
+            // ask for the packet again
+            self.stream.write_all(b"-")?;
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn write_packet(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+// the width in bytes of a register
+fn register_width(n: usize) -> usize {
+    match n {
+        REG_I | REG_PC => 2,
+        _ => 1,
+    }
+}
+
+fn ok_or_error(result: Option<()>) -> String {
+    match result {
+        Some(()) => "OK".to_string(),
+        None => "E01".to_string(),
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text, 16).ok()
+}
+
+// parses two hex numbers separated by `separator`, such as `200,10`
+fn parse_pair(text: &str, separator: char) -> Option<(usize, usize)> {
+    let (a, b) = text.split_once(separator)?;
+
+    Some((parse_hex(a)?, parse_hex(b)?))
+}
@@ -0,0 +1,229 @@
+use std::fmt;
+
+use super::parse_hex;
+use crate::emulator::{Access, Emulator, Watchpoint};
+
+/// A value that can be used in a condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Reg(u8),    // the value of VX
+    Index,      // the value of I
+    Value(u16), // a constant
+}
+
+/// How the two sides of a condition are compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A comparison that has to hold for a watch to stop the program, such as `V3 == 0x10`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Condition {
+    pub lhs: Operand,
+    pub compare: Compare,
+    pub rhs: Operand,
+}
+
+/// What a watch looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Memory(Watchpoint), // accesses to a range of memory
+    Reg(u8),            // changes to VX
+    Index,              // changes to I
+}
+
+/// Something that stops the program when it is accessed or changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watch {
+    pub target: Target,
+    pub condition: Option<Condition>, // only stop when this holds afterwards
+}
+
+impl Operand {
+    /// Parses `v0` to `vf`, `i`, or a number in hex
+    pub fn parse(text: &str) -> Option<Self> {
+        let lower = text.to_ascii_lowercase();
+
+        if lower == "i" {
+            return Some(Self::Index);
+        }
+
+        if let Some(reg) = parse_reg(&lower) {
+            return Some(Self::Reg(reg));
+        }
+
+        parse_hex(&lower)
+            .and_then(|value| u16::try_from(value).ok())
+            .map(Self::Value)
+    }
+
+    /// Gets the current value of the operand
+    pub fn value(&self, emu: &Emulator) -> u16 {
+        match *self {
+            Self::Reg(x) => emu.reg[x as usize] as u16,
+            Self::Index => emu.index as u16,
+            Self::Value(value) => value,
+        }
+    }
+}
+
+impl Compare {
+    fn parse(text: &str) -> Option<Self> {
+        Some(match text {
+            "==" => Self::Eq,
+            "!=" => Self::Ne,
+            "<" => Self::Lt,
+            "<=" => Self::Le,
+            ">" => Self::Gt,
+            ">=" => Self::Ge,
+            _ => return None,
+        })
+    }
+
+    fn symbol(&self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        }
+    }
+}
+
+impl Condition {
+    /// Parses a condition written as three words, such as `["v3", "==", "10"]`
+    pub fn parse(words: &[&str]) -> Result<Self, String> {
+        let [lhs, compare, rhs] = words else {
+            return Err("conditions look like `v3 == 10`".to_string());
+        };
+
+        let operand = |text: &str| Operand::parse(text).ok_or(format!("invalid operand `{text}`"));
+
+        Ok(Self {
+            lhs: operand(lhs)?,
+            compare: Compare::parse(compare).ok_or(format!("invalid comparison `{compare}`"))?,
+            rhs: operand(rhs)?,
+        })
+    }
+
+    /// Whether the condition currently holds
+    pub fn holds(&self, emu: &Emulator) -> bool {
+        let (lhs, rhs) = (self.lhs.value(emu), self.rhs.value(emu));
+
+        match self.compare {
+            Compare::Eq => lhs == rhs,
+            Compare::Ne => lhs != rhs,
+            Compare::Lt => lhs < rhs,
+            Compare::Le => lhs <= rhs,
+            Compare::Gt => lhs > rhs,
+            Compare::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl Watch {
+    /// Parses the arguments of a watch command
+    ///
+    /// # Arguments
+    ///
+    /// * `access` - Which memory accesses to stop on, registers always stop on changes
+    /// * `args` - A register, I, or an address with an optional length, then optionally
+    ///   `if` and a condition
+    /// * `memory_size` - Size of the memory being watched, ranges have to fit inside it
+    pub fn parse(access: Access, args: &[&str], memory_size: usize) -> Result<Self, String> {
+        let (target, condition) = match args.iter().position(|&arg| arg == "if") {
+            Some(split) => (&args[..split], Some(Condition::parse(&args[split + 1..])?)),
+            None => (args, None),
+        };
+
+        // only plain watches can be on registers, since registers are never read through memory
+        let register = match *target {
+            [text] if access == Access::Write => match Operand::parse(text) {
+                Some(Operand::Reg(x)) => Some(Target::Reg(x)),
+                Some(Operand::Index) => Some(Target::Index),
+                _ => None,
+            },
+            _ => None,
+        };
+
+        let target = match (register, target) {
+            (Some(register), _) => register,
+            (None, [addr]) => Target::Memory(Watchpoint {
+                addr: parse_hex(addr).ok_or(format!("invalid address `{addr}`"))?,
+                len: 1,
+                access,
+            }),
+            (None, [addr, len]) => Target::Memory(Watchpoint {
+                addr: parse_hex(addr).ok_or(format!("invalid address `{addr}`"))?,
+                len: len
+                    .parse()
+                    .ok()
+                    .filter(|&len| len > 0)
+                    .ok_or(format!("invalid length `{len}`"))?,
+                access,
+            }),
+            _ => return Err("watch needs a register, I or an address".to_string()),
+        };
+
+        if let Target::Memory(Watchpoint { addr, len, .. }) = target {
+            if addr.checked_add(len).is_none_or(|end| end > memory_size) {
+                return Err(format!(
+                    "0x{addr:03X} {len} goes past the end of memory at 0x{memory_size:03X}"
+                ));
+            }
+        }
+
+        Ok(Self { target, condition })
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reg(x) => write!(f, "V{x:X}"),
+            Self::Index => write!(f, "I"),
+            Self::Value(value) => write!(f, "0x{value:02X}"),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    /// Writes the watch as the command that would create it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            Target::Memory(watchpoint) => write!(f, "{watchpoint}")?,
+            Target::Reg(x) => write!(f, "watch V{x:X}")?,
+            Target::Index => write!(f, "watch I")?,
+        }
+
+        if let Some(condition) = self.condition {
+            write!(
+                f,
+                " if {} {} {}",
+                condition.lhs,
+                condition.compare.symbol(),
+                condition.rhs
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// parses `v0` to `vf`, already in lowercase
+fn parse_reg(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix('v')?;
+
+    match u8::from_str_radix(digit, 16) {
+        Ok(x) if digit.len() == 1 => Some(x),
+        _ => None,
+    }
+}
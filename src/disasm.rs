@@ -0,0 +1,272 @@
+//! Disassembler for Chip-8 ROMs
+//!
+//! Code is found by following every path of execution from [`SCRIPT_ADDR`],
+//! anything that is never reached is treated as data
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::emulator::{decode, Instruction, SCRIPT_ADDR};
+
+/// A single line of disassembly, either an instruction or a data byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line {
+    Code {
+        addr: usize,
+        opcode: u16,
+        instruction: Instruction,
+    },
+    LongIndex {
+        addr: usize,
+        nnnn: u16, // the address in the word after F000
+    },
+    Data {
+        addr: usize,
+        byte: u8,
+    },
+}
+
+/// A disassembled ROM
+pub struct Disassembly {
+    pub lines: Vec<Line>,        // every instruction and data byte in address order
+    pub labels: BTreeSet<usize>, // addresses that are targets of jumps and calls
+}
+
+/// Disassembles a ROM as it would be loaded at [`SCRIPT_ADDR`]
+///
+/// # Arguments
+///
+/// * `rom` - The contents of the ROM file
+pub fn disassemble(rom: &[u8]) -> Disassembly {
+    let end = SCRIPT_ADDR + rom.len();
+    let in_rom = |addr: usize| addr >= SCRIPT_ADDR && addr < end;
+    let word_at = |addr: usize| {
+        let offset = addr - SCRIPT_ADDR;
+        u16::from_be_bytes([rom[offset], rom[offset + 1]])
+    };
+
+    let mut code: BTreeMap<usize, (u16, Instruction)> = BTreeMap::new();
+    let mut labels = BTreeSet::new();
+    let mut queue = vec![SCRIPT_ADDR];
+
+    while let Some(addr) = queue.pop() {
+        if code.contains_key(&addr) || !in_rom(addr) || !in_rom(addr + 1) {
+            continue;
+        }
+
+        let opcode = word_at(addr);
+
+        // an unknown word ends this path, it was most likely data
+        let Ok(instruction) = decode(opcode) else {
+            continue;
+        };
+
+        // F000 NNNN is the only instruction that is 4 bytes long
+        let next = if instruction == Instruction::LongIndex {
+            if !in_rom(addr + 3) {
+                continue;
+            }
+
+            addr + 4
+        } else {
+            addr + 2
+        };
+
+        code.insert(addr, (opcode, instruction));
+
+        match instruction {
+            Instruction::Jump { nnn } => {
+                labels.insert(nnn as usize);
+                queue.push(nnn as usize);
+            }
+            Instruction::Call { nnn } => {
+                labels.insert(nnn as usize);
+                queue.push(nnn as usize);
+                queue.push(next);
+            }
+            Instruction::JumpOffset { nnn } => {
+                // the real target depends on V0, so it can't be followed
+                labels.insert(nnn as usize);
+            }
+            Instruction::Return | Instruction::Exit => {}
+            Instruction::SkipEqImm { .. }
+            | Instruction::SkipNeImm { .. }
+            | Instruction::SkipEq { .. }
+            | Instruction::SkipNe { .. }
+            | Instruction::SkipKey { .. }
+            | Instruction::SkipNotKey { .. } => {
+                // skipping over F000 NNNN skips all 4 bytes
+                let long = in_rom(next + 1) && word_at(next) == 0xF000;
+
+                queue.push(next);
+                queue.push(next + if long { 4 } else { 2 });
+            }
+            _ => queue.push(next),
+        }
+    }
+
+    labels.retain(|addr| in_rom(*addr));
+
+    let mut lines = Vec::new();
+    let mut addr = SCRIPT_ADDR;
+
+    while addr < end {
+        if let Some((_, Instruction::LongIndex)) = code.get(&addr) {
+            lines.push(Line::LongIndex {
+                addr,
+                nnnn: word_at(addr + 2),
+            });
+
+            addr += 4;
+        } else if let Some((opcode, instruction)) = code.get(&addr) {
+            lines.push(Line::Code {
+                addr,
+                opcode: *opcode,
+                instruction: *instruction,
+            });
+
+            addr += 2;
+        } else {
+            lines.push(Line::Data {
+                addr,
+                byte: rom[addr - SCRIPT_ADDR],
+            });
+
+            addr += 1;
+        }
+    }
+
+    Disassembly { lines, labels }
+}
+
+/// Gets the name used for the label at `addr`
+pub fn label_name(addr: usize) -> String {
+    format!("L{addr:03X}")
+}
+
+impl Disassembly {
+    // writes the mnemonic for an instruction, using label names for known targets
+    fn write_mnemonic(&self, f: &mut fmt::Formatter<'_>, instruction: Instruction) -> fmt::Result {
+        let target = |nnn: u16| {
+            if self.labels.contains(&(nnn as usize)) {
+                label_name(nnn as usize)
+            } else {
+                format!("0x{nnn:03X}")
+            }
+        };
+
+        match instruction {
+            Instruction::Jump { nnn } => write!(f, "JP {}", target(nnn)),
+            Instruction::Call { nnn } => write!(f, "CALL {}", target(nnn)),
+            Instruction::JumpOffset { nnn } => write!(f, "JP V0, {}", target(nnn)),
+            other => write!(f, "{other}"),
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    /// Writes one line per instruction or data byte, with the address, raw value and mnemonic
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match *line {
+                Line::Code {
+                    addr,
+                    opcode,
+                    instruction,
+                } => {
+                    if self.labels.contains(&addr) {
+                        writeln!(f, "{}:", label_name(addr))?;
+                    }
+
+                    write!(f, "0x{addr:03X}  {opcode:04X}  ")?;
+                    self.write_mnemonic(f, instruction)?;
+                    writeln!(f)?;
+                }
+                Line::LongIndex { addr, nnnn } => {
+                    if self.labels.contains(&addr) {
+                        writeln!(f, "{}:", label_name(addr))?;
+                    }
+
+                    writeln!(f, "0x{addr:03X}  F000 {nnnn:04X}  LD I, 0x{nnnn:04X}")?;
+                }
+                Line::Data { addr, byte } => {
+                    if self.labels.contains(&addr) {
+                        writeln!(f, "{}:", label_name(addr))?;
+                    }
+
+                    writeln!(f, "0x{addr:03X}  {byte:02X}    DB 0x{byte:02X}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output() {
+        let rom = [
+            0x22, 0x08, // call the subroutine
+            0xF0, 0x00, 0x02, 0x0C, // point I at the data
+            0x12, 0x06, // loop forever
+            0xD0, 0x15, // subroutine, draws the data
+            0x00, 0xEE, //
+            0xFF, 0x81, // data
+        ];
+
+        let expected = "\
+0x200  2208  CALL L208
+0x202  F000 020C  LD I, 0x020C
+L206:
+0x206  1206  JP L206
+L208:
+0x208  D015  DRW V0, V1, 5
+0x20A  00EE  RET
+0x20C  FF    DB 0xFF
+0x20D  81    DB 0x81
+";
+
+        assert_eq!(disassemble(&rom).to_string(), expected);
+    }
+
+    #[test]
+    fn skips_over_long_index() {
+        // the skip lands after all 4 bytes of F000 NNNN, so the 1208 in its second word is
+        // never followed as a jump and 0x208 stays data
+        let rom = [0x30, 0x00, 0xF0, 0x00, 0x12, 0x08, 0x00, 0xFD, 0x00, 0xE0];
+        let disassembly = disassemble(&rom);
+
+        assert!(disassembly.labels.is_empty());
+        assert_eq!(
+            disassembly.lines,
+            [
+                Line::Code {
+                    addr: 0x200,
+                    opcode: 0x3000,
+                    instruction: Instruction::SkipEqImm { x: 0, nn: 0 },
+                },
+                Line::LongIndex {
+                    addr: 0x202,
+                    nnnn: 0x1208,
+                },
+                Line::Code {
+                    addr: 0x206,
+                    opcode: 0x00FD,
+                    instruction: Instruction::Exit,
+                },
+                Line::Data {
+                    addr: 0x208,
+                    byte: 0x00,
+                },
+                Line::Data {
+                    addr: 0x209,
+                    byte: 0xE0,
+                },
+            ]
+        );
+    }
+}
@@ -1,67 +1,141 @@
 mod display;
+mod error;
 mod input;
+mod instruction;
 mod memory;
+mod policy;
+mod quirks;
+mod random;
+mod rewind;
 mod runner;
+mod state;
+mod trace;
+mod watch;
 
-use std::collections::VecDeque;
+use std::{collections::VecDeque, path::PathBuf};
 
 pub use display::EmuDisplay;
+pub use error::Chip8Error;
+pub use input::KeyWait;
+pub use instruction::{decode, DecodeError, Instruction};
+pub use memory::{BIG_FONT_ADDR, FONT_ADDR, MEMORY_SIZE, SCRIPT_ADDR, XO_CHIP_MEMORY_SIZE};
+pub use policy::OpcodePolicy;
+pub use quirks::Quirks;
+pub use random::{RandomSource, Sequence, XorShift};
+pub use rewind::Rewind;
+pub use runner::StepInfo;
+pub use state::{StateError, STATE_VERSION};
+pub use trace::{Trace, TraceFormat};
+pub use watch::{Access, WatchHit, Watchpoint};
 
-use memory::SCRIPT_ADDR;
-use minifb::{Window, WindowOptions};
+use crate::frontend::{Frontend, Keypad};
+
+/// Where an emulator sends problems that don't stop the program, such as a save state that
+/// couldn't be written, leaving it to the caller to show them
+pub type Log = Box<dyn FnMut(&str) + Send>;
+
+// the XO-CHIP pitch that plays the audio pattern at 4000hz
+const DEFAULT_PITCH: u8 = 64;
 
 /// The main emulator which contains all components and runs logic
+///
+/// The emulator has no window of its own, the display is shown by whichever
+/// [`Frontend`] it is run with and keys are read from whichever [`Keypad`]
 pub struct Emulator {
-    pub memory: [u8; 4096],     // 4096 bytes of ram
-    pub display: EmuDisplay,    // display data will be adapted from here
-    pub window: Window,         // window object for simulating the screen
-    pub index: usize,           // index register, used to access memory
+    pub memory: Vec<u8>,           // 4096 bytes of ram, or up to 64KiB for XO-CHIP
+    pub display: EmuDisplay,       // display data will be adapted from here
+    pub index: usize,              // index register, used to access memory
     pub counter: usize, // program counter, the current place in memory that is being executed
-    pub stack: VecDeque<usize>, // used for returning from subroutines
-    pub timer: u8,      // delay timer, decremented at 60hz with display drawing
-    pub s_timer: u8,    // sound timer, beeps at nonzero values
-    pub reg: [u8; 16],  // general purpose registers
-    pub tick_us: u16,   // microseconds per tick (1428 for 700tps)
-    pub ips: bool,      // whether to shift in place for 8XY6 and 8XYE
+    pub stack: VecDeque<usize>, // return addresses for subroutines, oldest first, up to quirks.stack_depth
+    pub timer: u8,              // delay timer, decremented at 60hz with display drawing
+    pub s_timer: u8,            // sound timer, beeps at nonzero values
+    pub reg: [u8; 16],          // general purpose registers
+    pub flags: [u8; 16],        // SUPER-CHIP RPL user flags, saved and loaded by FX75 and FX85
+    pub keys: [bool; 16],       // which keys are held, a snapshot of the keypad taken every frame
+    pub key_wait: Option<KeyWait>, // the FX0A waiting for a key, if one is
+    pub tick_us: u16,           // microseconds per tick (1428 for 700tps)
+    pub quirks: Quirks,         // compatibility settings for the interpreter being emulated
+    pub unknown_opcodes: OpcodePolicy, // what happens when an invalid instruction is reached
+    pub vblank: bool,           // set by each 60hz tick, cleared by drawing when waiting for it
+    pub halted: bool,           // set by 00FD, no more instructions are executed
+    pub audio_pattern: [u8; 16], // XO-CHIP 1 bit audio samples loaded by F002, played while the sound timer is nonzero
+    pub pitch: u8,               // XO-CHIP playback pitch for the audio pattern, set by FX3A
+    pub rng: Box<dyn RandomSource>, // random number generator for CXNN, seeded randomly by default
+    pub rewind: Option<Rewind>,  // recent frames to step back through, if rewinding is enabled
+    pub watchpoints: Vec<Watchpoint>, // memory ranges whose accesses are recorded in watch_hits
+    pub watch_hits: Vec<WatchHit>, // accesses caught by watchpoints, left for the debugger to clear
+    pub trace: Option<Trace>,    // record of every executed instruction, if tracing
+    pub state_path: Option<PathBuf>, // numbered save state slots are stored next to this path, usually the ROM
+    pub log: Option<Log>, // told about problems that don't stop the program, which are dropped without it
 }
 
 impl Emulator {
-    pub fn new(display: EmuDisplay, tick_us: u16, ips: bool) -> Self {
-        let dim = match display {
-            EmuDisplay::Chip8(_) => (64, 32),
-            EmuDisplay::SuperChip(_) => (128, 64),
-        };
-
-        let mut window = Window::new(
-            "Bee Chip-8 :)",
-            dim.0 * 4,
-            dim.1 * 4,
-            WindowOptions::default(),
-        )
-        .unwrap();
-
-        window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
-
+    pub fn new(display: EmuDisplay, tick_us: u16, quirks: Quirks) -> Self {
         Emulator {
-            memory: [0; 4096],
+            memory: vec![0; MEMORY_SIZE],
             display,
-            window,
             index: 0,
             counter: SCRIPT_ADDR,
-            stack: VecDeque::new(),
+            stack: VecDeque::with_capacity(quirks.stack_depth),
             timer: 255,
             s_timer: 255,
             reg: [0; 16],
+            flags: [0; 16],
+            keys: [false; 16],
+            key_wait: None,
             tick_us,
-            ips,
+            quirks,
+            unknown_opcodes: OpcodePolicy::default(),
+            vblank: true,
+            halted: false,
+            audio_pattern: [0; 16],
+            pitch: DEFAULT_PITCH,
+            rng: Box::new(XorShift::from_entropy()),
+            rewind: None,
+            watchpoints: Vec::new(),
+            watch_hits: Vec::new(),
+            trace: None,
+            state_path: None,
+            log: None,
         }
     }
 
-    pub fn run_script(&mut self, script: impl AsRef<[u8]>) {
+    /// Replaces memory with `size` bytes of empty memory, such as [`XO_CHIP_MEMORY_SIZE`]
+    ///
+    /// The font and script need to be loaded again afterwards
+    pub fn set_memory_size(&mut self, size: usize) {
+        self.memory = vec![0; size];
+    }
+
+    /// Iterates over the return addresses of the subroutines being run, innermost first
+    pub fn call_stack(&self) -> impl Iterator<Item = usize> + '_ {
+        self.stack.iter().rev().copied()
+    }
+
+    /// Gets the file for a numbered save state slot, if [`Emulator::state_path`] is set
+    ///
+    /// Slot 1 for `pong.ch8` is `pong.1.state`
+    pub fn slot_path(&self, slot: u8) -> Option<PathBuf> {
+        let path = self.state_path.as_ref()?;
+
+        Some(path.with_extension(format!("{slot}.state")))
+    }
+
+    /// The rate in hz at which bits of the audio pattern are played, based on the pitch
+    pub fn audio_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - DEFAULT_PITCH as f64) / 48.0)
+    }
+
+    pub fn run_script(
+        &mut self,
+        script: impl AsRef<[u8]>,
+        frontend: &mut impl Frontend,
+        keypad: &mut impl Keypad,
+    ) -> Result<(), Chip8Error> {
         let script = script.as_ref();
 
         self.load_font();
-        self.load_script(script);
-        self.main_loop();
+        self.load_script(script)?;
+        self.main_loop(frontend, keypad)
     }
 }
@@ -1,132 +1,222 @@
 use bitvec::prelude::*;
 
-use super::Emulator;
-
-const CHIP8_SIZE: usize = 64 * 32;
 const SUPERCHIP_SIZE: usize = 128 * 64;
 
-// creates the type to be used for the display
-// a bitvec::BitArray, because the display is monochrome
-// this is a flat array, we need to make sure to account for that later
-type Chip8Display = BitArr!(for CHIP8_SIZE, in u64, Msb0);
-type SuperChipDisplay = BitArr!(for SUPERCHIP_SIZE, in u64, Msb0);
+// the number of XO-CHIP bitplanes
+pub const PLANES: usize = 2;
 
-/// Possible display types (sizes)
-pub enum EmuDisplay {
-    Chip8(Chip8Display),
-    SuperChip(SuperChipDisplay),
+// creates the type to be used for each plane of the display
+// a bitvec::BitArray, because each plane is monochrome
+// this is a flat array, we need to make sure to account for that later
+// it is always big enough for the 128x64 display, the 64x32 display only uses the start of it
+type Plane = BitArr!(for SUPERCHIP_SIZE, in u64, Msb0);
+
+/// The display, 64x32 for Chip-8 or 128x64 for SUPER-CHIP hires mode
+///
+/// There are two XO-CHIP bitplanes, so each pixel has one of four colors.
+/// Chip-8 and SUPER-CHIP programs only ever draw on the first plane
+pub struct EmuDisplay {
+    pub(super) planes: [Plane; PLANES], // one bit per pixel for each plane
+    pub(super) hires: bool,             // whether this is the 128x64 display
+    pub selected: u8, // bitmask of planes that drawing and scrolling affect, set by FN01
 }
 
 impl EmuDisplay {
     pub fn new(kind: &str) -> Self {
-        if kind == "chip8" {
-            let out: Chip8Display = bitarr!(u64, Msb0; 0; CHIP8_SIZE);
-            Self::Chip8(out)
+        Self {
+            planes: [bitarr!(u64, Msb0; 0; SUPERCHIP_SIZE); PLANES],
+            hires: kind != "chip8",
+            selected: 0b01,
+        }
+    }
+
+    /// Gets the width and height of the display in pixels
+    pub fn dimensions(&self) -> (usize, usize) {
+        if self.hires {
+            (128, 64)
         } else {
-            let out: SuperChipDisplay = bitarr!(u64, Msb0; 0; SUPERCHIP_SIZE);
-            Self::SuperChip(out)
+            (64, 32)
         }
     }
 
+    /// Iterates over every pixel, row by row, yielding its color
+    ///
+    /// The color has a bit set for each plane the pixel is lit on, so plain
+    /// Chip-8 programs only produce 0 and 1
+    pub fn pixels(&self) -> impl Iterator<Item = u8> + '_ {
+        let (width, height) = self.dimensions();
+
+        (0..width * height).map(|i| {
+            self.planes
+                .iter()
+                .enumerate()
+                .fold(0, |color, (plane, buf)| color | ((buf[i] as u8) << plane))
+        })
+    }
+
+    /// Clears the selected planes
     pub fn clear(&mut self) {
-        match self {
-            Self::Chip8(buf) => {
-                *buf = bitarr!(u64, Msb0; 0; CHIP8_SIZE);
-            }
-            Self::SuperChip(buf) => {
-                *buf = bitarr!(u64, Msb0; 0; SUPERCHIP_SIZE);
-            }
+        for buf in self.selected_planes() {
+            buf.fill(false);
         }
     }
 
-    pub fn draw(&mut self, sprite: &[u8], coords: (u8, u8)) -> u8 {
-        // a type for holding temporary "Scratch" data as bits to be iterated through
-        type Scratch = BitArr!(for 4, in u8, Msb0);
+    // gets each plane that is currently selected
+    fn selected_planes(&mut self) -> impl Iterator<Item = &mut Plane> {
+        let selected = self.selected;
 
-        let mut scratch: Scratch = bitarr!(u8, Msb0; 0; 4);
+        self.planes
+            .iter_mut()
+            .enumerate()
+            .filter(move |(plane, _)| selected & (1 << plane) != 0)
+            .map(|(_, buf)| buf)
+    }
 
-        // weird way to get dimensions for this display
-        let dim = match self {
-            Self::Chip8(_) => (64, 32),
-            Self::SuperChip(_) => (128, 64),
-        };
+    /// Draws an 8 pixel wide sprite by XORing it onto the display
+    ///
+    /// When more than one plane is selected, each plane takes the next
+    /// `sprite.len() / planes` bytes of the sprite
+    ///
+    /// Returns 1 if any lit pixel was turned off, otherwise 0
+    ///
+    /// # Arguments
+    ///
+    /// * `sprite` - Rows of the sprite, one byte per row with the leftmost pixel in the high bit
+    /// * `coords` - Position of the top left corner, wrapped to fit on the display
+    /// * `clip` - Whether pixels past the edges are dropped instead of wrapping to the other side
+    pub fn draw(&mut self, sprite: &[u8], coords: (u8, u8), clip: bool) -> u8 {
+        self.blit(sprite, 1, coords, clip)
+    }
 
-        let x = coords.0 % dim.0;
-        let mut y = coords.1 % dim.1;
-        let mut carry = 0;
+    /// Draws a 16 by 16 SUPER-CHIP sprite, two bytes per row, otherwise the same as [`draw`]
+    ///
+    /// [`draw`]: EmuDisplay::draw
+    pub fn draw_large(&mut self, sprite: &[u8], coords: (u8, u8), clip: bool) -> u8 {
+        self.blit(sprite, 2, coords, clip)
+    }
 
-        for byte in sprite {
-            let pos = x as usize + y as usize * dim.0 as usize;
-            let offset = if x < dim.0 - 8 { 8 } else { dim.0 - x } as usize;
+    /// The number of planes currently selected
+    pub fn selected_count(&self) -> usize {
+        self.selected.count_ones() as usize
+    }
+
+    // XORs a sprite made of rows that are `row_bytes` bytes wide onto each selected plane
+    fn blit(&mut self, sprite: &[u8], row_bytes: usize, coords: (u8, u8), clip: bool) -> u8 {
+        let (width, height) = self.dimensions();
+        let count = self.selected_count().max(1);
+        let len = sprite.len() / count;
 
-            // get the slice of memory from the
-            let buf = match self {
-                Self::Chip8(buf) => buf.get_mut(pos..pos + offset).unwrap(),
-                Self::SuperChip(buf) => buf.get_mut(pos..pos + offset).unwrap(),
-            };
+        // the starting position always wraps, only the rest of the sprite can be clipped
+        let x = coords.0 as usize % width;
+        let y = coords.1 as usize % height;
+        let mut carry = 0;
 
-            scratch.store_be(*byte);
+        for (n, buf) in self.selected_planes().enumerate() {
+            let data = &sprite[n * len..(n + 1) * len];
 
-            for i in 0..offset {
-                let mut bit = buf.get_mut(i).unwrap();
+            for (row, bytes) in data.chunks(row_bytes).enumerate() {
+                let mut py = y + row;
 
-                if scratch[i] && *bit {
-                    *bit = false;
-                    carry = 1;
-                } else {
-                    *bit ^= scratch[i];
+                if py >= height {
+                    if clip {
+                        break;
+                    }
+
+                    py %= height;
                 }
-            }
 
-            y += 1;
+                for i in 0..bytes.len() * 8 {
+                    let mut px = x + i;
+
+                    if px >= width {
+                        if clip {
+                            break;
+                        }
 
-            if y >= dim.1 {
-                break;
+                        px %= width;
+                    }
+
+                    if bytes[i / 8] & (0x80 >> (i % 8)) == 0 {
+                        continue;
+                    }
+
+                    let mut bit = buf.get_mut(px + py * width).unwrap();
+
+                    if *bit {
+                        carry = 1;
+                    }
+
+                    *bit = !*bit;
+                }
             }
         }
 
         carry
     }
-}
 
-impl Emulator {
-    pub fn refresh_display(&mut self) {
-        let full = 0x00_FF_FF_FF;
-        let empty = 0;
-
-        match self.display {
-            EmuDisplay::Chip8(buf) => {
-                let len = 64 * 32;
-                let mut output: Vec<u32> = vec![0; len];
-
-                for i in 0..len {
-                    if buf[i] {
-                        output[i] = full;
-                    } else {
-                        output[i] = empty;
-                    }
-                }
+    /// Moves the selected planes down by `rows`, leaving blank rows at the top
+    pub fn scroll_down(&mut self, rows: usize) {
+        let (width, height) = self.dimensions();
+        let shift = (rows * width).min(width * height);
+
+        for buf in self.selected_planes() {
+            let buf = &mut buf[..width * height];
+
+            buf.rotate_right(shift);
+            buf[..shift].fill(false);
+        }
+    }
+
+    /// Moves the selected planes up by `rows`, leaving blank rows at the bottom
+    pub fn scroll_up(&mut self, rows: usize) {
+        let (width, height) = self.dimensions();
+        let shift = (rows * width).min(width * height);
+
+        for buf in self.selected_planes() {
+            let buf = &mut buf[..width * height];
+
+            buf.rotate_left(shift);
+            buf[width * height - shift..].fill(false);
+        }
+    }
+
+    /// Moves the selected planes right by `cols`, leaving blank columns on the left
+    pub fn scroll_right(&mut self, cols: usize) {
+        let (width, height) = self.dimensions();
+        let shift = cols.min(width);
 
-                self.window
-                    .update_with_buffer(&output[0..], 64, 32)
-                    .unwrap();
+        for buf in self.selected_planes() {
+            for row in buf[..width * height].chunks_mut(width) {
+                row.rotate_right(shift);
+                row[..shift].fill(false);
             }
-            EmuDisplay::SuperChip(buf) => {
-                let len = 128 * 64;
-                let mut output: Vec<u32> = vec![0; len];
-
-                for i in 0..len {
-                    if buf[i] {
-                        output[i] = full;
-                    } else {
-                        output[i] = empty;
-                    }
-                }
+        }
+    }
+
+    /// Moves the selected planes left by `cols`, leaving blank columns on the right
+    pub fn scroll_left(&mut self, cols: usize) {
+        let (width, height) = self.dimensions();
+        let shift = cols.min(width);
 
-                self.window
-                    .update_with_buffer(&output[0..], 128, 64)
-                    .unwrap();
+        for buf in self.selected_planes() {
+            for row in buf[..width * height].chunks_mut(width) {
+                row.rotate_left(shift);
+                row[width - shift..].fill(false);
             }
         }
     }
+
+    /// Whether this is the 128x64 SUPER-CHIP display
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switches between the 64x32 and 128x64 displays, clearing every plane
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+
+        for buf in &mut self.planes {
+            buf.fill(false);
+        }
+    }
 }
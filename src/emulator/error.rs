@@ -0,0 +1,44 @@
+use std::{error::Error, fmt};
+
+/// Everything that can go wrong while loading or running a program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    RomTooLarge { size: usize, capacity: usize }, // the ROM doesn't fit between SCRIPT_ADDR and the end of memory
+    MemoryOutOfBounds { pc: usize, addr: usize }, // an instruction accessed memory past the end
+    StackOverflow { pc: usize }, // a subroutine was called with the stack already full
+    StackUnderflow { pc: usize }, // a subroutine returned with nothing on the stack
+    UnknownOpcode { pc: usize, opcode: u16 }, // a word that isn't a valid instruction was executed
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RomTooLarge { size, capacity } => {
+                write!(
+                    f,
+                    "ROM is {size} bytes, but only {capacity} bytes fit in memory"
+                )
+            }
+            Self::MemoryOutOfBounds { pc, addr } => {
+                write!(
+                    f,
+                    "instruction at {pc:#05X} accessed {addr:#06X}, past the end of memory"
+                )
+            }
+            Self::StackOverflow { pc } => {
+                write!(f, "stack overflow calling a subroutine at {pc:#05X}")
+            }
+            Self::StackUnderflow { pc } => {
+                write!(
+                    f,
+                    "stack underflow returning from a subroutine at {pc:#05X}"
+                )
+            }
+            Self::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {opcode:04X} at {pc:#05X}")
+            }
+        }
+    }
+}
+
+impl Error for Chip8Error {}
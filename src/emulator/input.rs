@@ -1,55 +1,62 @@
 use super::Emulator;
-use bimap::BiMap;
-use minifb::Key;
 
-impl Emulator {
-    fn get_keycodes() -> BiMap<u8, Key> {
-        BiMap::from_iter([
-            (0x0, Key::X),
-            (0x1, Key::Key1),
-            (0x2, Key::Key2),
-            (0x3, Key::Key3),
-            (0x4, Key::Q),
-            (0x5, Key::W),
-            (0x6, Key::E),
-            (0x7, Key::A),
-            (0x8, Key::S),
-            (0x9, Key::D),
-            (0xA, Key::Z),
-            (0xB, Key::C),
-            (0xC, Key::Key4),
-            (0xD, Key::R),
-            (0xE, Key::F),
-            (0xF, Key::V),
-        ])
-    }
+/// An FX0A instruction that is waiting for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyWait {
+    pub pc: usize,           // address of the FX0A doing the waiting
+    pub stale: [bool; 16], // keys held since before the wait started, which only count once released
+    pub pressed: Option<u8>, // key that was pressed and has to be released, with the key_release quirk
+}
 
+impl Emulator {
     pub fn scan_key(&self, key: u8) -> bool {
-        let keycodes = Self::get_keycodes();
-        let Some(key) = keycodes.get_by_left(&key) else { return false };
+        let Some(pressed) = self.keys.get(key as usize) else {
+            return false;
+        };
 
-        self.window.is_key_down(*key)
+        *pressed
     }
 
-    pub fn scan_any(&self) -> Option<u8> {
-        let keycodes = Self::get_keycodes();
-        let pressed = self.window.get_keys();
-
-        dbg!(self.scan_key(0x3));
-
-        let key = keycodes.get_by_left(&0x3);
-
-        dbg!(key);
-        dbg!(&pressed);
-
-        for key in pressed {
-            dbg!(key);
+    // checks on the FX0A at `pc`, returning the key once the wait is over
+    //
+    // keys are only seen once per frame, so comparing with the keys held on earlier runs of the
+    // same instruction finds new presses and releases, rather than registering a held key again
+    pub(super) fn wait_key(&mut self, pc: usize) -> Option<u8> {
+        let keys = self.keys;
+
+        let wait = match &mut self.key_wait {
+            Some(wait) if wait.pc == pc => wait,
+            wait => wait.insert(KeyWait {
+                pc,
+                stale: keys,
+                pressed: None,
+            }),
+        };
+
+        // held keys count again once they've been let go
+        for (stale, held) in wait.stale.iter_mut().zip(keys) {
+            *stale &= held;
+        }
 
-            if let Some(code) = keycodes.get_by_right(&key) {
-                return Some(*code);
+        let key = match wait.pressed {
+            Some(key) if keys[key as usize] => None,
+            Some(key) => Some(key),
+            None => {
+                let pressed = (0..16).find(|&key| keys[key] && !wait.stale[key])? as u8;
+
+                if self.quirks.key_release {
+                    wait.pressed = Some(pressed);
+                    None
+                } else {
+                    Some(pressed)
+                }
             }
+        };
+
+        if key.is_some() {
+            self.key_wait = None;
         }
 
-        None
+        key
     }
 }
@@ -0,0 +1,316 @@
+use std::{error::Error, fmt};
+
+/// A single decoded instruction
+///
+/// Operands are named after the nibbles they come from, `x` and `y` are
+/// register numbers, `n`, `nn` and `nnn` are immediate values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    ScrollDown { n: u8 },         // 00CN
+    ScrollUp { n: u8 },           // 00DN
+    ClearScreen,                  // 00E0
+    Return,                       // 00EE
+    ScrollRight,                  // 00FB
+    ScrollLeft,                   // 00FC
+    Exit,                         // 00FD
+    Lores,                        // 00FE
+    Hires,                        // 00FF
+    Jump { nnn: u16 },            // 1NNN
+    Call { nnn: u16 },            // 2NNN
+    SkipEqImm { x: u8, nn: u8 },  // 3XNN
+    SkipNeImm { x: u8, nn: u8 },  // 4XNN
+    SkipEq { x: u8, y: u8 },      // 5XY0
+    SaveRange { x: u8, y: u8 },   // 5XY2
+    LoadRange { x: u8, y: u8 },   // 5XY3
+    SetImm { x: u8, nn: u8 },     // 6XNN
+    AddImm { x: u8, nn: u8 },     // 7XNN
+    Set { x: u8, y: u8 },         // 8XY0
+    Or { x: u8, y: u8 },          // 8XY1
+    And { x: u8, y: u8 },         // 8XY2
+    Xor { x: u8, y: u8 },         // 8XY3
+    Add { x: u8, y: u8 },         // 8XY4
+    Sub { x: u8, y: u8 },         // 8XY5
+    ShiftRight { x: u8, y: u8 },  // 8XY6
+    SubReverse { x: u8, y: u8 },  // 8XY7
+    ShiftLeft { x: u8, y: u8 },   // 8XYE
+    SkipNe { x: u8, y: u8 },      // 9XY0
+    SetIndex { nnn: u16 },        // ANNN
+    JumpOffset { nnn: u16 },      // BNNN
+    Random { x: u8, nn: u8 },     // CXNN
+    Draw { x: u8, y: u8, n: u8 }, // DXYN
+    SkipKey { x: u8 },            // EX9E
+    SkipNotKey { x: u8 },         // EXA1
+    LongIndex,                    // F000 NNNN, the address is the word after the instruction
+    Plane { n: u8 },              // FN01
+    Audio,                        // F002
+    GetDelay { x: u8 },           // FX07
+    WaitKey { x: u8 },            // FX0A
+    SetDelay { x: u8 },           // FX15
+    SetSound { x: u8 },           // FX18
+    AddIndex { x: u8 },           // FX1E
+    FontChar { x: u8 },           // FX29
+    BigFontChar { x: u8 },        // FX30
+    Pitch { x: u8 },              // FX3A
+    Bcd { x: u8 },                // FX33
+    Store { x: u8 },              // FX55
+    Load { x: u8 },               // FX65
+    SaveFlags { x: u8 },          // FX75
+    LoadFlags { x: u8 },          // FX85
+}
+
+impl Instruction {
+    /// Encodes the instruction back into its word, the inverse of [`decode`]
+    pub fn encode(self) -> u16 {
+        use Instruction::*;
+
+        // builds a word from its nibbles, with `nn` taking the last two
+        let xy = |n1: u16, x: u8, y: u8, n4: u16| {
+            (n1 << 12) | ((x as u16) << 8) | ((y as u16) << 4) | n4
+        };
+        let xnn = |n1: u16, x: u8, nn: u8| (n1 << 12) | ((x as u16) << 8) | nn as u16;
+
+        match self {
+            ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+            ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+            ClearScreen => 0x00E0,
+            Return => 0x00EE,
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Lores => 0x00FE,
+            Hires => 0x00FF,
+            Jump { nnn } => 0x1000 | (nnn & 0xFFF),
+            Call { nnn } => 0x2000 | (nnn & 0xFFF),
+            SkipEqImm { x, nn } => xnn(0x3, x, nn),
+            SkipNeImm { x, nn } => xnn(0x4, x, nn),
+            SkipEq { x, y } => xy(0x5, x, y, 0x0),
+            SaveRange { x, y } => xy(0x5, x, y, 0x2),
+            LoadRange { x, y } => xy(0x5, x, y, 0x3),
+            SetImm { x, nn } => xnn(0x6, x, nn),
+            AddImm { x, nn } => xnn(0x7, x, nn),
+            Set { x, y } => xy(0x8, x, y, 0x0),
+            Or { x, y } => xy(0x8, x, y, 0x1),
+            And { x, y } => xy(0x8, x, y, 0x2),
+            Xor { x, y } => xy(0x8, x, y, 0x3),
+            Add { x, y } => xy(0x8, x, y, 0x4),
+            Sub { x, y } => xy(0x8, x, y, 0x5),
+            ShiftRight { x, y } => xy(0x8, x, y, 0x6),
+            SubReverse { x, y } => xy(0x8, x, y, 0x7),
+            ShiftLeft { x, y } => xy(0x8, x, y, 0xE),
+            SkipNe { x, y } => xy(0x9, x, y, 0x0),
+            SetIndex { nnn } => 0xA000 | (nnn & 0xFFF),
+            JumpOffset { nnn } => 0xB000 | (nnn & 0xFFF),
+            Random { x, nn } => xnn(0xC, x, nn),
+            Draw { x, y, n } => xy(0xD, x, y, n as u16 & 0xF),
+            SkipKey { x } => xnn(0xE, x, 0x9E),
+            SkipNotKey { x } => xnn(0xE, x, 0xA1),
+            LongIndex => 0xF000,
+            Plane { n } => xnn(0xF, n, 0x01),
+            Audio => 0xF002,
+            GetDelay { x } => xnn(0xF, x, 0x07),
+            WaitKey { x } => xnn(0xF, x, 0x0A),
+            SetDelay { x } => xnn(0xF, x, 0x15),
+            SetSound { x } => xnn(0xF, x, 0x18),
+            AddIndex { x } => xnn(0xF, x, 0x1E),
+            FontChar { x } => xnn(0xF, x, 0x29),
+            BigFontChar { x } => xnn(0xF, x, 0x30),
+            Pitch { x } => xnn(0xF, x, 0x3A),
+            Bcd { x } => xnn(0xF, x, 0x33),
+            Store { x } => xnn(0xF, x, 0x55),
+            Load { x } => xnn(0xF, x, 0x65),
+            SaveFlags { x } => xnn(0xF, x, 0x75),
+            LoadFlags { x } => xnn(0xF, x, 0x85),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    /// Writes the instruction as an assembly mnemonic, such as `DRW V0, V1, 5`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            ScrollDown { n } => write!(f, "SCD {n}"),
+            ScrollUp { n } => write!(f, "SCU {n}"),
+            ClearScreen => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Lores => write!(f, "LOW"),
+            Hires => write!(f, "HIGH"),
+            Jump { nnn } => write!(f, "JP 0x{nnn:03X}"),
+            Call { nnn } => write!(f, "CALL 0x{nnn:03X}"),
+            SkipEqImm { x, nn } => write!(f, "SE V{x:X}, 0x{nn:02X}"),
+            SkipNeImm { x, nn } => write!(f, "SNE V{x:X}, 0x{nn:02X}"),
+            SkipEq { x, y } => write!(f, "SE V{x:X}, V{y:X}"),
+            SaveRange { x, y } => write!(f, "SAVE V{x:X}-V{y:X}"),
+            LoadRange { x, y } => write!(f, "LOAD V{x:X}-V{y:X}"),
+            SetImm { x, nn } => write!(f, "LD V{x:X}, 0x{nn:02X}"),
+            AddImm { x, nn } => write!(f, "ADD V{x:X}, 0x{nn:02X}"),
+            Set { x, y } => write!(f, "LD V{x:X}, V{y:X}"),
+            Or { x, y } => write!(f, "OR V{x:X}, V{y:X}"),
+            And { x, y } => write!(f, "AND V{x:X}, V{y:X}"),
+            Xor { x, y } => write!(f, "XOR V{x:X}, V{y:X}"),
+            Add { x, y } => write!(f, "ADD V{x:X}, V{y:X}"),
+            Sub { x, y } => write!(f, "SUB V{x:X}, V{y:X}"),
+            ShiftRight { x, y } => write!(f, "SHR V{x:X}, V{y:X}"),
+            SubReverse { x, y } => write!(f, "SUBN V{x:X}, V{y:X}"),
+            ShiftLeft { x, y } => write!(f, "SHL V{x:X}, V{y:X}"),
+            SkipNe { x, y } => write!(f, "SNE V{x:X}, V{y:X}"),
+            SetIndex { nnn } => write!(f, "LD I, 0x{nnn:03X}"),
+            JumpOffset { nnn } => write!(f, "JP V0, 0x{nnn:03X}"),
+            Random { x, nn } => write!(f, "RND V{x:X}, 0x{nn:02X}"),
+            Draw { x, y, n } => write!(f, "DRW V{x:X}, V{y:X}, {n}"),
+            SkipKey { x } => write!(f, "SKP V{x:X}"),
+            SkipNotKey { x } => write!(f, "SKNP V{x:X}"),
+            LongIndex => write!(f, "LD I, LONG"),
+            Plane { n } => write!(f, "PLANE {n}"),
+            Audio => write!(f, "AUDIO"),
+            GetDelay { x } => write!(f, "LD V{x:X}, DT"),
+            WaitKey { x } => write!(f, "LD V{x:X}, K"),
+            SetDelay { x } => write!(f, "LD DT, V{x:X}"),
+            SetSound { x } => write!(f, "LD ST, V{x:X}"),
+            AddIndex { x } => write!(f, "ADD I, V{x:X}"),
+            FontChar { x } => write!(f, "LD F, V{x:X}"),
+            BigFontChar { x } => write!(f, "LD HF, V{x:X}"),
+            Pitch { x } => write!(f, "PITCH V{x:X}"),
+            Bcd { x } => write!(f, "LD B, V{x:X}"),
+            Store { x } => write!(f, "LD [I], V{x:X}"),
+            Load { x } => write!(f, "LD V{x:X}, [I]"),
+            SaveFlags { x } => write!(f, "LD R, V{x:X}"),
+            LoadFlags { x } => write!(f, "LD V{x:X}, R"),
+        }
+    }
+}
+
+/// Returned when a word does not match any known instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16, // the word that failed to decode
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:04X}", self.opcode)
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Decodes a single instruction word
+///
+/// # Arguments
+///
+/// * `opcode` - The instruction as a big endian word, as it is laid out in memory
+pub fn decode(opcode: u16) -> Result<Instruction, DecodeError> {
+    use Instruction::*;
+
+    // get each nibble of the instruction
+    let n1 = ((opcode & 0xF000) >> 12) as u8;
+    let n2 = ((opcode & 0x0F00) >> 8) as u8;
+    let n3 = ((opcode & 0x00F0) >> 4) as u8;
+    let n4 = (opcode & 0x000F) as u8;
+
+    // prepare values before matching instructions
+    // all instructions with operands use some combination of this set
+    let x = n2;
+    let y = n3;
+    let n = n4;
+    let nn = (opcode & 0x00FF) as u8;
+    let nnn = opcode & 0x0FFF;
+
+    let instruction = match (n1, n2, n3, n4) {
+        (0x0, 0x0, 0xC, _) => ScrollDown { n },
+        (0x0, 0x0, 0xD, _) => ScrollUp { n },
+        (0x0, 0x0, 0xE, 0x0) => ClearScreen,
+        (0x0, 0x0, 0xE, 0xE) => Return,
+        (0x0, 0x0, 0xF, 0xB) => ScrollRight,
+        (0x0, 0x0, 0xF, 0xC) => ScrollLeft,
+        (0x0, 0x0, 0xF, 0xD) => Exit,
+        (0x0, 0x0, 0xF, 0xE) => Lores,
+        (0x0, 0x0, 0xF, 0xF) => Hires,
+        (0x1, ..) => Jump { nnn },
+        (0x2, ..) => Call { nnn },
+        (0x3, ..) => SkipEqImm { x, nn },
+        (0x4, ..) => SkipNeImm { x, nn },
+        (0x5, _, _, 0x0) => SkipEq { x, y },
+        (0x5, _, _, 0x2) => SaveRange { x, y },
+        (0x5, _, _, 0x3) => LoadRange { x, y },
+        (0x6, ..) => SetImm { x, nn },
+        (0x7, ..) => AddImm { x, nn },
+        (0x8, _, _, 0x0) => Set { x, y },
+        (0x8, _, _, 0x1) => Or { x, y },
+        (0x8, _, _, 0x2) => And { x, y },
+        (0x8, _, _, 0x3) => Xor { x, y },
+        (0x8, _, _, 0x4) => Add { x, y },
+        (0x8, _, _, 0x5) => Sub { x, y },
+        (0x8, _, _, 0x6) => ShiftRight { x, y },
+        (0x8, _, _, 0x7) => SubReverse { x, y },
+        (0x8, _, _, 0xE) => ShiftLeft { x, y },
+        (0x9, _, _, 0x0) => SkipNe { x, y },
+        (0xA, ..) => SetIndex { nnn },
+        (0xB, ..) => JumpOffset { nnn },
+        (0xC, ..) => Random { x, nn },
+        (0xD, ..) => Draw { x, y, n },
+        (0xE, _, 0x9, 0xE) => SkipKey { x },
+        (0xE, _, 0xA, 0x1) => SkipNotKey { x },
+        (0xF, 0x0, 0x0, 0x0) => LongIndex,
+        (0xF, _, 0x0, 0x1) => Plane { n: x },
+        (0xF, 0x0, 0x0, 0x2) => Audio,
+        (0xF, _, 0x0, 0x7) => GetDelay { x },
+        (0xF, _, 0x0, 0xA) => WaitKey { x },
+        (0xF, _, 0x1, 0x5) => SetDelay { x },
+        (0xF, _, 0x1, 0x8) => SetSound { x },
+        (0xF, _, 0x1, 0xE) => AddIndex { x },
+        (0xF, _, 0x2, 0x9) => FontChar { x },
+        (0xF, _, 0x3, 0x0) => BigFontChar { x },
+        (0xF, _, 0x3, 0xA) => Pitch { x },
+        (0xF, _, 0x3, 0x3) => Bcd { x },
+        (0xF, _, 0x5, 0x5) => Store { x },
+        (0xF, _, 0x6, 0x5) => Load { x },
+        (0xF, _, 0x7, 0x5) => SaveFlags { x },
+        (0xF, _, 0x8, 0x5) => LoadFlags { x },
+        _ => return Err(DecodeError { opcode }),
+    };
+
+    Ok(instruction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_opcode_round_trips() {
+        let mut known = 0;
+
+        for opcode in 0..=0xFFFF {
+            match decode(opcode) {
+                Ok(instruction) => {
+                    assert_eq!(instruction.encode(), opcode, "{instruction}");
+                    known += 1;
+                }
+                Err(err) => assert_eq!(err.opcode, opcode),
+            }
+        }
+
+        // every word starting with 1, 2, 3, 4, 6, 7, A, B, C or D is an instruction,
+        // along with a handful of patterns from each of the other groups
+        assert!(known > 10 * 0x1000, "only {known} opcodes decoded");
+    }
+
+    #[test]
+    fn operands_come_from_the_right_nibbles() {
+        assert_eq!(decode(0x8AB4), Ok(Instruction::Add { x: 0xA, y: 0xB }));
+        assert_eq!(
+            decode(0x3C7F),
+            Ok(Instruction::SkipEqImm { x: 0xC, nn: 0x7F })
+        );
+        assert_eq!(decode(0xA123), Ok(Instruction::SetIndex { nnn: 0x123 }));
+        assert_eq!(decode(0xD125), Ok(Instruction::Draw { x: 1, y: 2, n: 5 }));
+        assert_eq!(decode(0xF201), Ok(Instruction::Plane { n: 2 }));
+        assert_eq!(decode(0x5121), Err(DecodeError { opcode: 0x5121 }));
+        assert_eq!(decode(0xE19F), Err(DecodeError { opcode: 0xE19F }));
+    }
+}
@@ -1,4 +1,10 @@
-use super::Emulator;
+use super::{Chip8Error, Emulator};
+
+// the amount of memory on the original interpreters
+pub const MEMORY_SIZE: usize = 4096;
+
+// the amount of memory XO-CHIP programs can use
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
 
 // the address to store the font at
 pub const FONT_ADDR: usize = 0x50;
@@ -25,19 +31,43 @@ const FONT: [u8; FONT_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+// the address to store the SUPER-CHIP big font at, right after the small font
+pub const BIG_FONT_ADDR: usize = FONT_ADDR + FONT_SIZE;
+
+// the big font is 160 bytes (10 bytes by 16 chars)
+const BIG_FONT_SIZE: usize = 160;
+
+const BIG_FONT: [u8; BIG_FONT_SIZE] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
 // the address to store scripts at
 pub const SCRIPT_ADDR: usize = 0x200;
 
 impl Emulator {
-    /// Loads the font into memory
+    /// Loads the small and big fonts into memory
     ///
     /// # Arguments
     ///
     /// * `memory` - Simulated memory to load the font into
     pub fn load_font(&mut self) {
-        for i in 0..FONT_SIZE {
-            self.memory[FONT_ADDR + i] = FONT[i];
-        }
+        self.memory[FONT_ADDR..FONT_ADDR + FONT_SIZE].copy_from_slice(&FONT);
+        self.memory[BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONT_SIZE].copy_from_slice(&BIG_FONT);
     }
 
     /// Loads a script into memory
@@ -46,27 +76,95 @@ impl Emulator {
     ///
     /// * `memory` - Simulated memory to load the script into
     /// * `script` - Script to load into memory and then execute
-    pub fn load_script(&mut self, script: &[u8]) {
-        for i in 0..script.len() {
-            self.memory[SCRIPT_ADDR + i] = script[i];
+    pub fn load_script(&mut self, script: &[u8]) -> Result<(), Chip8Error> {
+        let capacity = self.memory.len().saturating_sub(SCRIPT_ADDR);
+
+        if script.len() > capacity {
+            return Err(Chip8Error::RomTooLarge {
+                size: script.len(),
+                capacity,
+            });
+        }
+
+        self.memory[SCRIPT_ADDR..SCRIPT_ADDR + script.len()].copy_from_slice(script);
+
+        Ok(())
+    }
+
+    /// Reads `len` bytes of memory starting at `addr`
+    ///
+    /// This is meant to be used while executing an instruction, errors and
+    /// watchpoint hits blame the instruction just before the program counter,
+    /// and errors halt with the program counter moved back onto it.
+    /// Use [`peek`] to look at memory without triggering watchpoints
+    ///
+    /// [`peek`]: Emulator::peek
+    pub fn read_mem(&mut self, addr: usize, len: usize) -> Result<&[u8], Chip8Error> {
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, len, false);
+        }
+
+        if addr + len > self.memory.len() {
+            return Err(self.out_of_bounds(addr + len - 1));
         }
+
+        Ok(&self.memory[addr..addr + len])
     }
 
-    pub fn set_mem(&mut self, block: impl AsRef<[u8]>) {
-        let block = block.as_ref();
+    /// Writes `block` to memory starting at `addr`, in the same way as [`read_mem`]
+    ///
+    /// [`read_mem`]: Emulator::read_mem
+    pub fn write_mem(&mut self, addr: usize, block: &[u8]) -> Result<(), Chip8Error> {
+        if !self.watchpoints.is_empty() {
+            self.check_watchpoints(addr, block.len(), true);
+        }
 
-        for i in 0..block.len() {
-            self.memory[self.index + i] = block[i];
+        if addr + block.len() > self.memory.len() {
+            return Err(self.out_of_bounds(addr + block.len() - 1));
         }
+
+        self.memory[addr..addr + block.len()].copy_from_slice(block);
+
+        Ok(())
     }
 
-    pub fn load_mem(&self, len: usize) -> Vec<u8> {
-        let mut out = Vec::new();
+    /// Reads the big endian word at `addr`, in the same way as [`read_mem`]
+    ///
+    /// [`read_mem`]: Emulator::read_mem
+    pub fn read_word(&mut self, addr: usize) -> Result<u16, Chip8Error> {
+        let bytes = self.read_mem(addr, 2)?;
+
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
 
-        for i in 0..=len {
-            out.push(self.memory[self.index + i]);
+    /// Gets `len` bytes of memory starting at `addr` without triggering watchpoints,
+    /// or `None` if they go past the end
+    pub fn peek(&self, addr: usize, len: usize) -> Option<&[u8]> {
+        self.memory.get(addr..addr.checked_add(len)?)
+    }
+
+    /// Gets the big endian word at `addr` in the same way as [`peek`]
+    ///
+    /// [`peek`]: Emulator::peek
+    pub fn peek_word(&self, addr: usize) -> Option<u16> {
+        let bytes = self.peek(addr, 2)?;
+
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    // halts on the instruction being executed, which the program counter has already moved past
+    fn out_of_bounds(&mut self, addr: usize) -> Chip8Error {
+        Chip8Error::MemoryOutOfBounds {
+            pc: self.trap(),
+            addr,
         }
+    }
+
+    pub fn set_mem(&mut self, block: impl AsRef<[u8]>) -> Result<(), Chip8Error> {
+        self.write_mem(self.index, block.as_ref())
+    }
 
-        out
+    pub fn load_mem(&mut self, len: usize) -> Result<Vec<u8>, Chip8Error> {
+        Ok(self.read_mem(self.index, len + 1)?.to_vec())
     }
 }
@@ -0,0 +1,27 @@
+/// What to do when the program counter reaches a word that isn't a valid instruction
+///
+/// Ignoring them matches most interpreters, but hides bugs in ROMs that are
+/// still being written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpcodePolicy {
+    #[default]
+    Ignore, // skip over the word as if it were a no-op
+    Log,  // skip over it, but tell `Emulator::log` where it was
+    Halt, // stop with a `Chip8Error::UnknownOpcode`
+}
+
+impl OpcodePolicy {
+    /// Gets a policy by name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - One of `ignore`, `log` or `halt`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ignore" => Some(Self::Ignore),
+            "log" => Some(Self::Log),
+            "halt" => Some(Self::Halt),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+/// Behaviours that differ between Chip-8 interpreters
+///
+/// Most ROMs are written for one interpreter and depend on its quirks, so
+/// they are grouped into presets named after the interpreter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    pub shift: bool,        // 8XY6 and 8XYE shift VX in place instead of copying VY first
+    pub jump: bool,         // BNNN is BXNN, jumping to XNN plus VX instead of NNN plus V0
+    pub memory: bool,       // FX55 and FX65 leave I pointing past the last register
+    pub logic: bool,        // 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub clip: bool,         // sprites are clipped at the edges instead of wrapping around
+    pub display_wait: bool, // DXYN waits for the next 60hz tick, allowing one draw per frame
+    pub stack_depth: usize, // how many subroutine calls can be nested
+    pub stack_wrap: bool,   // a full stack drops its oldest entry instead of overflowing
+    pub key_release: bool,  // FX0A waits for the key to be released, instead of just pressed
+}
+
+impl Quirks {
+    /// The original interpreter on the COSMAC VIP
+    pub const COSMAC_VIP: Self = Self {
+        shift: false,
+        jump: false,
+        memory: true,
+        logic: true,
+        clip: true,
+        display_wait: true,
+        stack_depth: 12,
+        stack_wrap: false,
+        key_release: true,
+    };
+
+    /// CHIP-48 on the HP 48 calculators
+    pub const CHIP_48: Self = Self {
+        shift: true,
+        jump: true,
+        memory: false,
+        logic: false,
+        clip: true,
+        display_wait: false,
+        stack_depth: 16,
+        stack_wrap: false,
+        key_release: false,
+    };
+
+    /// SUPER-CHIP 1.1, also on the HP 48
+    pub const SUPER_CHIP: Self = Self {
+        shift: true,
+        jump: true,
+        memory: false,
+        logic: false,
+        clip: true,
+        display_wait: false,
+        stack_depth: 16,
+        stack_wrap: false,
+        key_release: false,
+    };
+
+    /// XO-CHIP, as implemented by Octo
+    pub const XO_CHIP: Self = Self {
+        shift: false,
+        jump: false,
+        memory: true,
+        logic: false,
+        clip: false,
+        display_wait: false,
+        stack_depth: 16,
+        stack_wrap: false,
+        key_release: true,
+    };
+
+    /// Used when no preset is picked, CHIP-48 but with BNNN jumping to NNN plus V0 like
+    /// the emulator did before presets existed, so older ROMs keep running the same way
+    pub const DEFAULT: Self = Self {
+        jump: false,
+        ..Self::CHIP_48
+    };
+
+    /// Names accepted by [`Quirks::from_name`], with the preset they select
+    pub const PRESETS: [(&'static str, Self); 4] = [
+        ("vip", Self::COSMAC_VIP),
+        ("chip48", Self::CHIP_48),
+        ("schip", Self::SUPER_CHIP),
+        ("xochip", Self::XO_CHIP),
+    ];
+
+    // the quirks as stored in save states and movies, a byte for each flag then the stack depth
+    pub(crate) fn to_bytes(self) -> [u8; 10] {
+        let depth = (self.stack_depth as u16).to_be_bytes();
+
+        [
+            self.shift as u8,
+            self.jump as u8,
+            self.memory as u8,
+            self.logic as u8,
+            self.clip as u8,
+            self.display_wait as u8,
+            self.stack_wrap as u8,
+            self.key_release as u8,
+            depth[0],
+            depth[1],
+        ]
+    }
+
+    // reads quirks written by to_bytes
+    pub(crate) fn from_bytes(bytes: [u8; 10]) -> Self {
+        Self {
+            shift: bytes[0] != 0,
+            jump: bytes[1] != 0,
+            memory: bytes[2] != 0,
+            logic: bytes[3] != 0,
+            clip: bytes[4] != 0,
+            display_wait: bytes[5] != 0,
+            stack_wrap: bytes[6] != 0,
+            key_release: bytes[7] != 0,
+            stack_depth: u16::from_be_bytes([bytes[8], bytes[9]]) as usize,
+        }
+    }
+
+    /// Gets a preset by name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - One of `vip`, `chip48`, `schip` or `xochip`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+            .map(|(_, quirks)| *quirks)
+    }
+}
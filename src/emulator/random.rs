@@ -0,0 +1,94 @@
+/// Where CXNN gets its random numbers from
+///
+/// The state of a source has to fit in a single number, so it can be saved and
+/// restored along with the rest of the machine
+pub trait RandomSource: Send {
+    /// Generates the next random byte
+    fn next_u8(&mut self) -> u8;
+
+    /// The current state, which can be passed to [`RandomSource::restore`] to continue from here
+    fn state(&self) -> u64;
+
+    /// Continues from a state returned by [`RandomSource::state`]
+    fn restore(&mut self, state: u64);
+}
+
+/// Small xorshift generator used by CXNN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorShift {
+    state: u64, // never zero, since xorshift would only ever produce zero from it
+}
+
+impl XorShift {
+    /// Creates a generator from a seed, or from the state of another generator
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Starting state, zero is replaced with one
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    /// Creates a generator with a random seed
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+impl RandomSource for XorShift {
+    fn next_u8(&mut self) -> u8 {
+        // xorshift64*
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+
+        (self.state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn restore(&mut self, state: u64) {
+        self.state = state.max(1);
+    }
+}
+
+/// A source that gives out a fixed list of bytes in order, starting over at the end
+///
+/// Useful for tests that need to know exactly what CXNN will produce
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    bytes: Vec<u8>,  // the bytes given out, never empty
+    position: usize, // index of the next byte
+}
+
+impl Sequence {
+    /// Creates a source that repeats `bytes`, an empty list gives out zeroes
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        let mut bytes = bytes.into();
+
+        if bytes.is_empty() {
+            bytes.push(0);
+        }
+
+        Self { bytes, position: 0 }
+    }
+}
+
+impl RandomSource for Sequence {
+    fn next_u8(&mut self) -> u8 {
+        let byte = self.bytes[self.position];
+        self.position = (self.position + 1) % self.bytes.len();
+
+        byte
+    }
+
+    fn state(&self) -> u64 {
+        self.position as u64
+    }
+
+    fn restore(&mut self, state: u64) {
+        self.position = (state % self.bytes.len() as u64) as usize;
+    }
+}
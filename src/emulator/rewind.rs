@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use super::Emulator;
+
+// unchanged bytes shorter than this between two changes are stored as part of one run,
+// since each run costs 8 bytes of header
+const MERGE_GAP: usize = 8;
+
+/// Ring buffer of recent save states, for playing a program backward
+///
+/// Only the newest state is kept whole, every older one is stored as the
+/// bytes that changed between it and the state after it. Frames rarely
+/// change much of memory, so this keeps minutes of history small
+pub struct Rewind {
+    current: Option<Vec<u8>>, // the newest state, the deltas lead backward from it
+    deltas: VecDeque<Vec<u8>>, // each one turns a state into the one before it, oldest first
+    capacity: usize,          // the most deltas to keep, older ones are dropped
+}
+
+impl Rewind {
+    /// Creates an empty buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - How many frames can be stepped back, 60 per second
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            current: None,
+            deltas: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Records a new state, dropping the oldest one when the buffer is full
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - State made by [`Emulator::save_state`](super::Emulator::save_state)
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(current) = &self.current {
+            if self.capacity > 0 {
+                if self.deltas.len() == self.capacity {
+                    self.deltas.pop_front();
+                }
+
+                self.deltas.push_back(diff(&state, current));
+            }
+        }
+
+        self.current = Some(state);
+    }
+
+    /// Steps back one state, returning it so it can be loaded
+    ///
+    /// Returns `None` once the oldest state is reached
+    pub fn pop(&mut self) -> Option<&[u8]> {
+        let delta = self.deltas.pop_back()?;
+        let current = self.current.as_mut()?;
+
+        patch(current, &delta);
+
+        Some(current)
+    }
+
+    /// The number of states that can be stepped back through
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Forgets every recorded state
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+    }
+}
+
+// encodes the changes that turn `from` into `to`
+// the length of `to` comes first, then runs of (offset from the end of the last run, length, bytes)
+fn diff(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let changed = |i: usize| from.get(i) != Some(&to[i]);
+
+    delta.extend_from_slice(&(to.len() as u32).to_be_bytes());
+
+    let mut last = 0;
+    let mut i = 0;
+
+    while i < to.len() {
+        if !changed(i) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut end = i + 1;
+
+        // extend the run until there is a long enough stretch of unchanged bytes
+        while end < to.len() {
+            if changed(end) {
+                end += 1;
+                continue;
+            }
+
+            let next = (end..to.len().min(end + MERGE_GAP)).find(|&j| changed(j));
+
+            match next {
+                Some(j) => end = j + 1,
+                None => break,
+            }
+        }
+
+        delta.extend_from_slice(&((start - last) as u32).to_be_bytes());
+        delta.extend_from_slice(&((end - start) as u32).to_be_bytes());
+        delta.extend_from_slice(&to[start..end]);
+
+        last = end;
+        i = end;
+    }
+
+    delta
+}
+
+// applies a delta made by `diff` to the state it was made from
+fn patch(state: &mut Vec<u8>, delta: &[u8]) {
+    let word = |at: usize| u32::from_be_bytes(delta[at..at + 4].try_into().unwrap()) as usize;
+
+    state.resize(word(0), 0);
+
+    let mut pos = 0;
+    let mut at = 4;
+
+    while at < delta.len() {
+        let start = pos + word(at);
+        let len = word(at + 4);
+        at += 8;
+
+        state[start..start + len].copy_from_slice(&delta[at..at + len]);
+
+        pos = start + len;
+        at += len;
+    }
+}
+
+impl Emulator {
+    /// Records the current state in the rewind buffer, if rewinding is enabled
+    pub fn record_frame(&mut self) {
+        if self.rewind.is_none() {
+            return;
+        }
+
+        let state = self.save_state();
+
+        if let Some(rewind) = &mut self.rewind {
+            rewind.push(state);
+        }
+    }
+
+    /// Goes back to the previous recorded frame
+    ///
+    /// Returns whether there was a frame to go back to
+    pub fn step_back(&mut self) -> bool {
+        let Some(state) = self.rewind.as_mut().and_then(Rewind::pop) else {
+            return false;
+        };
+
+        // recorded states were made by this emulator, so they always load
+        let state = state.to_vec();
+        self.load_state(&state).is_ok()
+    }
+}
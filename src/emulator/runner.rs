@@ -1,281 +1,682 @@
-use rand::Rng;
+use super::{
+    instruction::{decode, Instruction},
+    memory::{BIG_FONT_ADDR, FONT_ADDR},
+    Chip8Error, Emulator, OpcodePolicy,
+};
+use crate::frontend::{Command, Frontend, Keypad};
 
-use super::{memory::FONT_ADDR, Emulator};
+// microseconds in one 60hz frame
+const FRAME_US: u32 = 16666;
 
-use std::time::Instant;
+/// What happened while executing a single instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepInfo {
+    pub opcode: u16,           // the raw instruction word that was executed
+    pub pc_before: usize,      // program counter when the instruction was fetched
+    pub pc_after: usize,       // program counter after executing
+    pub display_changed: bool, // whether the display buffer was modified
+}
 
 impl Emulator {
-    /// Main emulator loop, runs the program loaded in memory
-    pub fn main_loop(&mut self) {
-        // this will be updated each cycle, so we can know when enough time has passed for a new one
-        let mut tick = Instant::now();
-        let mut display_tick = Instant::now();
+    /// Main emulator loop, runs the program loaded in memory until the frontend closes
+    /// or the keypad runs out of input
+    ///
+    /// Time is counted in instructions, each frame runs [`Self::cycles_per_frame`] of them
+    /// then ticks the timers, so a run only depends on its input. Keeping to real time is
+    /// left to [`Frontend::wait_frame`]
+    ///
+    /// Stops early if the program halts or an instruction fails
+    ///
+    /// # Arguments
+    ///
+    /// * `frontend` - Where the display is shown and commands come from
+    /// * `keypad` - Where keys are read from at the start of each frame
+    pub fn main_loop(
+        &mut self,
+        frontend: &mut (impl Frontend + ?Sized),
+        keypad: &mut (impl Keypad + ?Sized),
+    ) -> Result<(), Chip8Error> {
+        while frontend.is_open() && !keypad.is_finished() && !self.halted {
+            self.read_keypad(keypad);
+
+            let commands = frontend.commands();
+            let rewinding = commands.contains(&Command::Rewind);
+
+            for command in commands {
+                self.run_command(command);
+            }
+
+            // time stands still while going backward
+            if !rewinding {
+                self.run_frame()?;
+                self.record_frame();
+            }
+
+            // refreshed every frame even when nothing was drawn, since windows read input while refreshing
+            frontend.refresh(&self.display);
+            frontend.wait_frame();
+        }
+
+        Ok(())
+    }
+
+    // carries out a command from the frontend
+    // failures are only logged, since the program can keep running either way
+    fn run_command(&mut self, command: Command) {
+        let slot = match command {
+            Command::SaveState(slot) | Command::LoadState(slot) => slot,
+            Command::Rewind => {
+                self.step_back();
+                return;
+            }
+        };
+
+        let Some(path) = self.slot_path(slot) else {
+            self.report("save states need a state path");
+            return;
+        };
+
+        let result = match command {
+            Command::LoadState(_) => self.load_state_file(&path),
+            _ => self.save_state_file(&path),
+        };
+
+        if let Err(err) = result {
+            self.report(&format!("{}: {err}", path.display()));
+        }
+    }
+
+    // passes a problem that doesn't stop the program to the log, if there is one
+    fn report(&mut self, message: &str) {
+        if let Some(log) = &mut self.log {
+            log(message);
+        }
+    }
+
+    /// How many instructions are executed during one 60hz frame
+    pub fn cycles_per_frame(&self) -> u32 {
+        (FRAME_US / self.tick_us.max(1) as u32).max(1)
+    }
+
+    /// Decrements the delay and sound timers and signals vblank, this should happen at 60hz
+    pub fn tick_timers(&mut self) {
+        self.vblank = true;
+        self.timer = self.timer.wrapping_sub(1);
+        self.s_timer = self.s_timer.wrapping_sub(1);
+    }
+
+    /// Executes a full frame worth of instructions, then ticks the timers
+    ///
+    /// Returns whether the display changed during the frame
+    pub fn run_frame(&mut self) -> Result<bool, Chip8Error> {
+        let display_changed = self.run_cycles(self.cycles_per_frame())?;
+
+        self.tick_timers();
+
+        Ok(display_changed)
+    }
+
+    /// Executes `cycles` instructions without touching the timers
+    ///
+    /// Returns whether the display changed during any of them
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - Number of instructions to execute
+    pub fn run_cycles(&mut self, cycles: u32) -> Result<bool, Chip8Error> {
         let mut display_changed = false;
-        let mut rng = rand::thread_rng();
 
-        loop {
-            // when 1/60th of a second has passed, refresh display
-            if display_tick.elapsed().as_micros() >= 16666 {
-                if display_changed {
-                    self.refresh_display();
-                }
+        for _ in 0..cycles {
+            if self.halted {
+                break;
+            }
+
+            display_changed |= self.step()?.display_changed;
+        }
+
+        Ok(display_changed)
+    }
+
+    /// Takes a snapshot of the keys for the next frame
+    pub fn read_keypad(&mut self, keypad: &mut (impl Keypad + ?Sized)) {
+        keypad.update();
+        self.keys = keypad.snapshot();
+    }
+
+    /// Executes `cycles` instructions as fast as possible, ticking the timers once per frame
+    ///
+    /// Stops early if the program halts or the keypad runs out of input
+    ///
+    /// # Arguments
+    ///
+    /// * `cycles` - Number of instructions to execute
+    /// * `keypad` - Where keys are read from at the start of each frame
+    pub fn run_for(
+        &mut self,
+        cycles: u64,
+        keypad: &mut (impl Keypad + ?Sized),
+    ) -> Result<(), Chip8Error> {
+        let mut remaining = cycles;
+
+        while remaining > 0 && !keypad.is_finished() && !self.halted {
+            self.read_keypad(keypad);
+
+            let frame = remaining.min(self.cycles_per_frame() as u64);
 
-                self.timer = self.timer.wrapping_sub(1);
-                self.s_timer = self.s_timer.wrapping_sub(1);
+            self.run_cycles(frame as u32)?;
+            remaining -= frame;
 
-                display_tick = Instant::now();
+            // a partial frame at the end doesn't get a tick
+            if frame == self.cycles_per_frame() as u64 {
+                self.tick_timers();
             }
+        }
+
+        Ok(())
+    }
 
-            if tick.elapsed().as_micros() < self.tick_us as u128 {
-                // keep waiting if a full tick hasnt passed yet
-                continue;
+    /// Fetches, decodes and executes exactly one instruction
+    ///
+    /// Once halted by 00FD nothing is executed and the program counter stays put
+    ///
+    /// Invalid instructions are handled according to [`Emulator::unknown_opcodes`]
+    pub fn step(&mut self) -> Result<StepInfo, Chip8Error> {
+        let opcode = match self.memory.get(self.counter..self.counter + 2) {
+            Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+            // the program counter is already on the word that can't be fetched
+            None => {
+                self.halted = true;
+
+                return Err(Chip8Error::MemoryOutOfBounds {
+                    pc: self.counter,
+                    addr: self.counter + 1,
+                });
             }
+        };
 
-            tick = Instant::now();
+        let mut step = StepInfo {
+            opcode,
+            pc_before: self.counter,
+            pc_after: self.counter,
+            display_changed: false,
+        };
 
-            let byte1 = self.memory[self.counter];
-            let byte2 = self.memory[self.counter + 1];
+        if self.halted {
+            return Ok(step);
+        }
 
-            self.counter += 2;
+        self.counter += 2;
 
-            // get the first and second halves (nibbles) of each instruction byte
-            let n1 = (byte1 & 0xF0) >> 4;
-            let n2 = byte1 & 0x0F;
-            let n3 = (byte2 & 0xF0) >> 4;
-            let n4 = byte2 & 0x0F;
+        let result = match decode(opcode) {
+            Ok(instruction) => self.execute(instruction),
+            Err(_) => self.unknown_opcode(opcode).map(|_| false),
+        };
 
-            // prepare values before matching instructions
-            // all instructions with operands use some combination of this set
-            let x = n2 as usize;
-            let y = n3 as usize;
-            let n = n4;
-            let nn = byte2;
-            let nnn = ((n2 as usize) << 8) + byte2 as usize;
+        // an instruction that fails is traced too, since it's usually the one worth looking at
+        step.pc_after = self.counter;
+        step.display_changed = matches!(result, Ok(true));
+        self.trace_step(&step);
 
-            match (n1, n2, n3, n4) {
-                (0x0, 0x0, 0xE, 0x0) => {
-                    // 00E0 - Clear screen
-                    self.display.clear();
-                    display_changed = true;
-                }
-                (0x0, 0x0, 0xE, 0xE) => {
-                    // 00EE - End subroutine
-                    // move to the last address on the stack
-                    self.counter = self.stack.pop_back().unwrap_or(self.counter);
-                }
-                (0x1, ..) => {
-                    // 1NNN - Jump
-                    // Jump to memory address `NNN`
-                    self.counter = nnn;
-                }
-                (0x2, ..) => {
-                    // 2NNN - Start subroutine
-                    self.stack.push_back(self.counter);
-                    self.counter = nnn;
-                }
-                (0x3, ..) => {
-                    // 3XNN - Skip if equal to immediate
-                    if self.reg[x] == nn {
-                        self.counter += 2;
-                    }
-                }
-                (0x4, ..) => {
-                    // 4XNN - Skip if not equal to immediate
-                    if self.reg[x] != nn {
-                        self.counter += 2;
-                    }
+        result.map(|_| step)
+    }
+
+    // handles an invalid instruction according to the unknown opcode policy
+    fn unknown_opcode(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        match self.unknown_opcodes {
+            OpcodePolicy::Ignore => Ok(()),
+            OpcodePolicy::Log => {
+                let pc = self.counter - 2;
+
+                self.report(&Chip8Error::UnknownOpcode { pc, opcode }.to_string());
+                Ok(())
+            }
+            OpcodePolicy::Halt => Err(Chip8Error::UnknownOpcode {
+                pc: self.trap(),
+                opcode,
+            }),
+        }
+    }
+
+    // halts with the program counter left on the instruction being executed, so it can be
+    // inspected, and returns its address
+    pub(super) fn trap(&mut self) -> usize {
+        self.counter -= 2;
+        self.halted = true;
+
+        self.counter
+    }
+
+    // skips the next instruction, which is 4 bytes long if it is F000 NNNN
+    fn skip(&mut self) {
+        let long = self.memory.get(self.counter..self.counter + 2) == Some(&[0xF0, 0x00]);
+
+        self.counter += if long { 4 } else { 2 };
+    }
+
+    // gets the registers from VX to VY, which goes backwards when X is greater than Y
+    fn reg_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
+    }
+
+    /// Executes a decoded instruction, the program counter should already point past it
+    ///
+    /// Returns whether the display changed, or an error if memory was accessed out of bounds
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - The instruction to execute
+    pub fn execute(&mut self, instruction: Instruction) -> Result<bool, Chip8Error> {
+        use Instruction::*;
+
+        let mut display_changed = false;
+
+        match instruction {
+            ScrollDown { n } => {
+                // 00CN - Scroll down
+                // moves the display down by N pixels
+                self.display.scroll_down(n as usize);
+                display_changed = true;
+            }
+            ScrollUp { n } => {
+                // 00DN - Scroll up
+                // moves the display up by N pixels
+                self.display.scroll_up(n as usize);
+                display_changed = true;
+            }
+            ClearScreen => {
+                // 00E0 - Clear screen
+                self.display.clear();
+                display_changed = true;
+            }
+            Return => {
+                // 00EE - End subroutine
+                // move to the last address on the stack
+                match self.stack.pop_back() {
+                    Some(addr) => self.counter = addr,
+                    // with a wrapping stack there is nothing to return to, so carry on
+                    None if self.quirks.stack_wrap => {}
+                    None => return Err(Chip8Error::StackUnderflow { pc: self.trap() }),
                 }
-                (0x5, _, _, 0x0) => {
-                    // 5XY0 - Skip if equal
-                    if self.reg[x] == self.reg[y] {
-                        self.counter += 2;
+            }
+            ScrollRight => {
+                // 00FB - Scroll right
+                // moves the display right by 4 pixels
+                self.display.scroll_right(4);
+                display_changed = true;
+            }
+            ScrollLeft => {
+                // 00FC - Scroll left
+                // moves the display left by 4 pixels
+                self.display.scroll_left(4);
+                display_changed = true;
+            }
+            Exit => {
+                // 00FD - Exit
+                // stops the interpreter, the program counter is left on this instruction
+                self.counter -= 2;
+                self.halted = true;
+            }
+            Lores => {
+                // 00FE - Low resolution
+                // switches to the 64x32 display
+                self.display.set_hires(false);
+                display_changed = true;
+            }
+            Hires => {
+                // 00FF - High resolution
+                // switches to the 128x64 display
+                self.display.set_hires(true);
+                display_changed = true;
+            }
+            Jump { nnn } => {
+                // 1NNN - Jump
+                // Jump to memory address `NNN`
+                self.counter = nnn as usize;
+            }
+            Call { nnn } => {
+                // 2NNN - Start subroutine
+                if self.stack.len() >= self.quirks.stack_depth {
+                    if !self.quirks.stack_wrap {
+                        return Err(Chip8Error::StackOverflow { pc: self.trap() });
                     }
+
+                    // the oldest return address is overwritten
+                    self.stack.pop_front();
                 }
-                (0x6, ..) => {
-                    // 6XNN - Set register
-                    // Set register `VX` to `NN`
-                    self.reg[x] = nn;
+
+                self.stack.push_back(self.counter);
+                self.counter = nnn as usize;
+            }
+            SkipEqImm { x, nn } => {
+                // 3XNN - Skip if equal to immediate
+                if self.reg[x as usize] == nn {
+                    self.skip();
                 }
-                (0x7, ..) => {
-                    // 7XNN - Add to register
-                    // Add `NN` to register `VX`
-                    self.reg[x] = self.reg[x].wrapping_add(nn);
+            }
+            SkipNeImm { x, nn } => {
+                // 4XNN - Skip if not equal to immediate
+                if self.reg[x as usize] != nn {
+                    self.skip();
                 }
-                (0x8, _, _, 0x0) => {
-                    // 8XY0 - Set to other register
-                    self.reg[x] = self.reg[y];
+            }
+            SkipEq { x, y } => {
+                // 5XY0 - Skip if equal
+                if self.reg[x as usize] == self.reg[y as usize] {
+                    self.skip();
                 }
-                (0x8, _, _, 0x1) => {
-                    // 8XY1 - OR
-                    self.reg[x] |= self.reg[y];
+            }
+            SaveRange { x, y } => {
+                // 5XY2 - Save register range
+                // stores VX through VY in memory starting at I, without changing I
+                let block: Vec<u8> = Self::reg_range(x, y).map(|reg| self.reg[reg]).collect();
+
+                self.write_mem(self.index, &block)?;
+            }
+            LoadRange { x, y } => {
+                // 5XY3 - Load register range
+                // loads VX through VY from memory starting at I, without changing I
+                let block = self
+                    .read_mem(self.index, x.abs_diff(y) as usize + 1)?
+                    .to_vec();
+
+                for (reg, value) in Self::reg_range(x, y).zip(block) {
+                    self.reg[reg] = value;
                 }
-                (0x8, _, _, 0x2) => {
-                    // 8XY2 - AND
-                    self.reg[x] &= self.reg[y];
+            }
+            SetImm { x, nn } => {
+                // 6XNN - Set register
+                // Set register `VX` to `NN`
+                self.reg[x as usize] = nn;
+            }
+            AddImm { x, nn } => {
+                // 7XNN - Add to register
+                // Add `NN` to register `VX`
+                self.reg[x as usize] = self.reg[x as usize].wrapping_add(nn);
+            }
+            Set { x, y } => {
+                // 8XY0 - Set to other register
+                self.reg[x as usize] = self.reg[y as usize];
+            }
+            Or { x, y } => {
+                // 8XY1 - OR
+                self.reg[x as usize] |= self.reg[y as usize];
+
+                if self.quirks.logic {
+                    self.reg[0xF] = 0;
                 }
-                (0x8, _, _, 0x3) => {
-                    // 8XY3 - XOR
-                    self.reg[x] ^= self.reg[y];
+            }
+            And { x, y } => {
+                // 8XY2 - AND
+                self.reg[x as usize] &= self.reg[y as usize];
+
+                if self.quirks.logic {
+                    self.reg[0xF] = 0;
                 }
-                (0x8, _, _, 0x4) => {
-                    // 8XY4 - Add registers
-                    self.reg[x] = self.reg[x].wrapping_add(self.reg[y]);
+            }
+            Xor { x, y } => {
+                // 8XY3 - XOR
+                self.reg[x as usize] ^= self.reg[y as usize];
+
+                if self.quirks.logic {
+                    self.reg[0xF] = 0;
                 }
-                (0x8, _, _, 0x5) => {
-                    // 8XY5 - Subtract Y from X
-                    // set carry flag if overflowed
-                    self.reg[0xF] = if self.reg[y] > self.reg[x] { 1 } else { 0 };
-                    self.reg[x] = self.reg[x].wrapping_sub(self.reg[y]);
+            }
+            Add { x, y } => {
+                // 8XY4 - Add registers
+                self.reg[x as usize] = self.reg[x as usize].wrapping_add(self.reg[y as usize]);
+            }
+            Sub { x, y } => {
+                // 8XY5 - Subtract Y from X
+                // set carry flag if overflowed
+                self.reg[0xF] = if self.reg[y as usize] > self.reg[x as usize] {
+                    1
+                } else {
+                    0
+                };
+                self.reg[x as usize] = self.reg[x as usize].wrapping_sub(self.reg[y as usize]);
+            }
+            ShiftRight { x, y } => {
+                // 8XY6 - Shift right
+                // the shift quirk means x should be shifted in place.
+                // without it, y will be moved into x before being shifted
+                if !self.quirks.shift {
+                    self.reg[x as usize] = self.reg[y as usize];
                 }
-                (0x8, _, _, 0x6) => {
-                    // 8XY6 - Shift right
-                    // ips (in place shifting) means x should be shifted.
-                    // without it, y will be moved into x before being shifted
-                    if !self.ips {
-                        self.reg[x] = self.reg[y];
-                    }
 
-                    self.reg[0xF] = self.reg[x] & 0b1;
-                    self.reg[x] >>= 1;
-                }
-                (0x8, _, _, 0x7) => {
-                    // 8XY7 - Subtract X from Y
-                    // set carry flag if overflowed
-                    self.reg[0xF] = if self.reg[x] > self.reg[y] { 1 } else { 0 };
-                    self.reg[y] = self.reg[y].wrapping_sub(self.reg[x]);
+                self.reg[0xF] = self.reg[x as usize] & 0b1;
+                self.reg[x as usize] >>= 1;
+            }
+            SubReverse { x, y } => {
+                // 8XY7 - Subtract X from Y
+                // set carry flag if overflowed
+                self.reg[0xF] = if self.reg[x as usize] > self.reg[y as usize] {
+                    1
+                } else {
+                    0
+                };
+                self.reg[y as usize] = self.reg[y as usize].wrapping_sub(self.reg[x as usize]);
+            }
+            ShiftLeft { x, y } => {
+                // 8XYE - Shift left
+                // the shift quirk means x should be shifted in place.
+                // without it, y will be moved into x before being shifted
+                if !self.quirks.shift {
+                    self.reg[x as usize] = self.reg[y as usize];
                 }
-                (0x8, _, _, 0xE) => {
-                    // 8XYE - Shift left
-                    // ips (in place shifting) means x should be shifted.
-                    // without it, y will be moved into x before being shifted
-                    if !self.ips {
-                        self.reg[x] = self.reg[y];
-                    }
 
-                    self.reg[0xF] = self.reg[x] & 0b1000_0000;
-                    self.reg[x] <<= 1;
+                self.reg[0xF] = self.reg[x as usize] & 0b1000_0000;
+                self.reg[x as usize] <<= 1;
+            }
+            SkipNe { x, y } => {
+                // 9XY0 - Skip if not equal
+                if self.reg[x as usize] != self.reg[y as usize] {
+                    self.skip();
                 }
-                (0x9, _, _, 0x0) => {
-                    // 9XY0 - Skip if not equal
-                    if self.reg[x] != self.reg[y] {
-                        self.counter += 2;
+            }
+            JumpOffset { nnn } => {
+                // BNNN - Jump with offset
+                // this jumps to V0 offset by NNN bytes
+                // with the jump quirk it is BXNN instead, using VX where X is the top nibble of NNN
+                let offset = if self.quirks.jump {
+                    self.reg[(nnn >> 8) as usize]
+                } else {
+                    self.reg[0]
+                };
+
+                self.counter = offset as usize + nnn as usize;
+            }
+            Random { x, nn } => {
+                // CXNN - Random
+                // a random u8 is generated and ANDed together with nn, then put in VX
+                self.reg[x as usize] = nn & self.rng.next_u8();
+            }
+            SetIndex { nnn } => {
+                // ANNN - Set index
+                // Set index register `I` to `NNN`
+                self.index = nnn as usize;
+            }
+            Draw { x, y, n } => {
+                // DXYN - Display
+                // Displays the sprite found in memory at I with height N in position (VX,VY)
+                if self.quirks.display_wait {
+                    if !self.vblank {
+                        // try again until the next 60hz tick
+                        self.counter -= 2;
+                        return Ok(false);
                     }
-                }
-                (0xB, ..) => {
-                    // BNNN - Jump with offset
-                    // this jumps to V0 offset by NNN bytes
-                    self.counter = self.reg[0] as usize + nnn;
-                }
-                (0xC, ..) => {
-                    // CXNN - Random
-                    // a random u8 is generated and ANDed together with nn, then put in VX
-                    self.reg[x] = nn & rng.gen::<u8>();
-                }
 
-                (0xA, ..) => {
-                    // ANNN - Set index
-                    // Set index register `I` to `NNN`
-                    self.index = nnn;
+                    self.vblank = false;
                 }
-                (0xD, ..) => {
-                    // DXYN - Display
-                    // Displays the sprite found in memory at I with height N in position (VX,VY)
-                    let coords = (self.reg[x], self.reg[y]);
 
+                let coords = (self.reg[x as usize], self.reg[y as usize]);
+
+                // with XO-CHIP, each selected plane gets its own copy of the sprite data
+                let planes = self.display.selected_count();
+
+                self.reg[0xF] = if n == 0 {
+                    // DXY0 - SUPER-CHIP 16x16 sprite
+                    // each row is two bytes, for 32 bytes total
+                    let sprite = self.read_mem(self.index, 32 * planes)?.to_vec();
+
+                    self.display.draw_large(&sprite, coords, self.quirks.clip)
+                } else {
                     // gets the sprite starting from I and going N pixels down
                     // each byte is a row of pixels
-                    let sprite = &self.memory[self.index..self.index + n as usize];
+                    let sprite = self.read_mem(self.index, n as usize * planes)?.to_vec();
 
-                    self.reg[0xF] = self.display.draw(sprite, coords);
-                    display_changed = true;
-                }
-                (0xE, _, 0x9, 0xE) => {
-                    // EX9E - Skip if key pressed
-                    if self.scan_key(self.reg[x]) {
-                        self.counter += 2;
-                    }
-                }
-                (0xE, _, 0xA, 0x1) => {
-                    // EXA1 - Skip if key not pressed
-                    if !self.scan_key(self.reg[x]) {
-                        self.counter += 2;
-                    }
-                }
-                (0xF, _, 0x0, 0x7) => {
-                    // FX07 - Set VX to delay timer
-                    self.reg[x] = self.timer;
-                }
-                (0xF, _, 0x0, 0xA) => {
-                    // FX0A - Get key
-                    // blocks until any key is pressed, then stores that key in VX
-                    // this is done by just looping back to this same instruction
-                    let scanned = self.scan_any();
-
-                    match scanned {
-                        Some(code) => self.reg[x] = code,
-                        None => self.counter -= 2,
-                    };
-                }
-                (0xF, _, 0x1, 0x5) => {
-                    // FX15 - Set delay timer to VX
-                    self.timer = self.reg[x];
-                }
-                (0xF, _, 0x1, 0x8) => {
-                    // FX18 - Set sound timer to VX
-                    self.s_timer = self.reg[x];
-                }
-                (0xF, _, 0x1, 0xE) => {
-                    // FX1E - Add to index
-                    // adds VX to I, setting the carry flag if I leaves memory
-                    self.index += self.reg[x] as usize;
-                    self.reg[0xF] = if self.index > 0xFFF { 1 } else { 0 };
-                }
-                (0xF, _, 0x2, 0x9) => {
-                    // FX29 - Font character
-                    // sets I to the location of the character in the last nibble of VX
-                    let to = self.reg[x] & 0x0F;
+                    self.display.draw(&sprite, coords, self.quirks.clip)
+                };
 
-                    // multiply by 5 because each character contains 5 bytes
-                    self.index = FONT_ADDR + to as usize * 5;
+                display_changed = true;
+            }
+            SkipKey { x } => {
+                // EX9E - Skip if key pressed
+                if self.scan_key(self.reg[x as usize]) {
+                    self.skip();
                 }
-                (0xF, _, 0x3, 0x3) => {
-                    // FX33 - Binary coded decimal conversion
-                    // stores the decimal representation of VX across I, I+1, and I+2
-                    // one digit per byte
-                    let val = self.reg[x];
-
-                    // 123
-                    // ones = 3
-                    // tens = 2
-                    // hundreds = 1
-                    let hundreds = val / 100;
-                    let tens = (val % 100) / 10;
-                    let ones = val % 10;
-
-                    self.set_mem(&[hundreds, tens, ones]);
+            }
+            SkipNotKey { x } => {
+                // EXA1 - Skip if key not pressed
+                if !self.scan_key(self.reg[x as usize]) {
+                    self.skip();
                 }
-                (0xF, _, 0x5, 0x5) => {
-                    // FX55 - Store memory
-                    // stores V0 through VX in memory
-                    let block = &self.reg[0..=x];
-                    let mut moving: Vec<u8> = vec![0; x + 1];
-
-                    moving.copy_from_slice(block);
-                    self.set_mem(moving);
+            }
+            LongIndex => {
+                // F000 NNNN - Long index
+                // sets I to the 16 bit address in the next word, then skips over it
+                self.index = self.read_word(self.counter)? as usize;
+                self.counter += 2;
+            }
+            Plane { n } => {
+                // FN01 - Select planes
+                // N is a bitmask of the planes that drawing, clearing and scrolling affect
+                self.display.selected = n & 0b11;
+            }
+            Audio => {
+                // F002 - Load audio pattern
+                // copies 16 bytes starting at I into the audio pattern buffer
+                let pattern = self.read_mem(self.index, 16)?.to_vec();
+
+                self.audio_pattern.copy_from_slice(&pattern);
+            }
+            GetDelay { x } => {
+                // FX07 - Set VX to delay timer
+                self.reg[x as usize] = self.timer;
+            }
+            WaitKey { x } => {
+                // FX0A - Get key
+                // blocks until a key is pressed, or pressed and released with the key_release
+                // quirk, then stores that key in VX
+                // this is done by just looping back to this same instruction
+                match self.wait_key(self.counter - 2) {
+                    Some(code) => self.reg[x as usize] = code,
+                    None => self.counter -= 2,
+                };
+            }
+            SetDelay { x } => {
+                // FX15 - Set delay timer to VX
+                self.timer = self.reg[x as usize];
+            }
+            SetSound { x } => {
+                // FX18 - Set sound timer to VX
+                self.s_timer = self.reg[x as usize];
+            }
+            AddIndex { x } => {
+                // FX1E - Add to index
+                // adds VX to I, setting the carry flag if I leaves memory, which is
+                // 64KiB rather than 4KiB in XO-CHIP mode
+                self.index += self.reg[x as usize] as usize;
+                self.reg[0xF] = if self.index >= self.memory.len() {
+                    1
+                } else {
+                    0
+                };
+            }
+            FontChar { x } => {
+                // FX29 - Font character
+                // sets I to the location of the character in the last nibble of VX
+                let to = self.reg[x as usize] & 0x0F;
+
+                // multiply by 5 because each character contains 5 bytes
+                self.index = FONT_ADDR + to as usize * 5;
+            }
+            BigFontChar { x } => {
+                // FX30 - Big font character
+                // sets I to the location of the 8x10 character in the last nibble of VX
+                let to = self.reg[x as usize] & 0x0F;
+
+                // multiply by 10 because each big character contains 10 bytes
+                self.index = BIG_FONT_ADDR + to as usize * 10;
+            }
+            Pitch { x } => {
+                // FX3A - Set pitch
+                // sets the playback rate of the audio pattern to VX
+                self.pitch = self.reg[x as usize];
+            }
+            Bcd { x } => {
+                // FX33 - Binary coded decimal conversion
+                // stores the decimal representation of VX across I, I+1, and I+2
+                // one digit per byte
+                let val = self.reg[x as usize];
+
+                // 123
+                // ones = 3
+                // tens = 2
+                // hundreds = 1
+                let hundreds = val / 100;
+                let tens = (val % 100) / 10;
+                let ones = val % 10;
+
+                self.set_mem([hundreds, tens, ones])?;
+            }
+            Store { x } => {
+                // FX55 - Store memory
+                // stores V0 through VX in memory
+                let x = x as usize;
+                let block = &self.reg[0..=x];
+                let mut moving: Vec<u8> = vec![0; x + 1];
+
+                moving.copy_from_slice(block);
+                self.set_mem(moving)?;
+
+                if self.quirks.memory {
+                    self.index += x + 1;
                 }
-                (0xF, _, 0x6, 0x5) => {
-                    // FX65 - Load memory
-                    // loads X bytes from memory into registers V0-VX
-                    let moving = self.load_mem(x);
+            }
+            Load { x } => {
+                // FX65 - Load memory
+                // loads X bytes from memory into registers V0-VX
+                let x = x as usize;
+                let moving = self.load_mem(x)?;
 
-                    for i in 0..=x {
-                        self.reg[i] = moving[i];
-                    }
+                self.reg[..=x].copy_from_slice(&moving[..=x]);
+
+                if self.quirks.memory {
+                    self.index += x + 1;
                 }
-                _ => {
-                    continue;
-                } // ignore unknown instructions
+            }
+            SaveFlags { x } => {
+                // FX75 - Save flags
+                // stores V0 through VX in the RPL user flags
+                let x = x as usize;
+
+                self.flags[..=x].copy_from_slice(&self.reg[..=x]);
+            }
+            LoadFlags { x } => {
+                // FX85 - Load flags
+                // loads V0 through VX from the RPL user flags
+                let x = x as usize;
+
+                self.reg[..=x].copy_from_slice(&self.flags[..=x]);
             }
         }
+
+        Ok(display_changed)
     }
 }
@@ -0,0 +1,274 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use super::{display::PLANES, Emulator, KeyWait, Quirks, MEMORY_SIZE, XO_CHIP_MEMORY_SIZE};
+
+// the first bytes of every save state file
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// The save state layout version, bumped whenever the layout changes
+///
+/// States from other versions are rejected instead of being misread
+pub const STATE_VERSION: u16 = 2;
+
+/// Everything that can go wrong while loading a save state
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),           // the file couldn't be read or written
+    NotAState,               // the data doesn't start with the save state magic
+    UnsupportedVersion(u16), // the state was saved with a different layout
+    Truncated,               // the data ended before the whole machine was read
+    Invalid(&'static str),   // a value in the state is impossible, names the field
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::NotAState => write!(f, "not a save state"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "save state is version {version}, but only version {STATE_VERSION} is supported"
+            ),
+            Self::Truncated => write!(f, "save state is truncated"),
+            Self::Invalid(field) => write!(f, "save state has an invalid {field}"),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+// reads the fields of a state in order
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.bytes.len() < len {
+            return Err(StateError::Truncated);
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+}
+
+impl Emulator {
+    /// Serializes the complete machine into a versioned save state
+    ///
+    /// This covers memory, registers, the stack, timers, the display, quirks,
+    /// the random number generator and any FX0A waiting for a key, but not the
+    /// held keys or run settings
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + 2048);
+
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&STATE_VERSION.to_be_bytes());
+
+        out.extend_from_slice(&self.quirks.to_bytes());
+
+        out.extend_from_slice(&(self.memory.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.memory);
+
+        out.extend_from_slice(&self.reg);
+        out.extend_from_slice(&self.flags);
+        out.extend_from_slice(&(self.index as u32).to_be_bytes());
+        out.extend_from_slice(&(self.counter as u32).to_be_bytes());
+
+        out.extend_from_slice(&(self.stack.len() as u16).to_be_bytes());
+        for addr in &self.stack {
+            out.extend_from_slice(&(*addr as u32).to_be_bytes());
+        }
+
+        out.extend_from_slice(&[
+            self.timer,
+            self.s_timer,
+            self.vblank as u8,
+            self.halted as u8,
+        ]);
+        out.extend_from_slice(&self.audio_pattern);
+        out.push(self.pitch);
+
+        out.extend_from_slice(&[self.display.hires as u8, self.display.selected]);
+        for plane in &self.display.planes {
+            for word in plane.as_raw_slice() {
+                out.extend_from_slice(&word.to_be_bytes());
+            }
+        }
+
+        out.extend_from_slice(&self.rng.state().to_be_bytes());
+
+        // a waiting FX0A as its address, a bit for each stale key, and the pressed key or 0xFF
+        match &self.key_wait {
+            Some(wait) => {
+                let stale = (0..16)
+                    .filter(|&key| wait.stale[key])
+                    .fold(0u16, |stale, key| stale | 1 << key);
+
+                out.push(1);
+                out.extend_from_slice(&(wait.pc as u32).to_be_bytes());
+                out.extend_from_slice(&stale.to_be_bytes());
+                out.push(wait.pressed.unwrap_or(0xFF));
+            }
+            None => out.push(0),
+        }
+
+        out
+    }
+
+    /// Restores the machine from a state made by [`Emulator::save_state`]
+    ///
+    /// The whole state is checked before anything is changed, so the emulator
+    /// is left as it was if it can't be loaded
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The saved state
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        let mut r = Reader { bytes: state };
+
+        if r.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(StateError::NotAState);
+        }
+
+        let version = r.u16()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let quirks = Quirks::from_bytes(r.array()?);
+
+        let memory_size = r.u32()? as usize;
+        if !(MEMORY_SIZE..=XO_CHIP_MEMORY_SIZE).contains(&memory_size) {
+            return Err(StateError::Invalid("memory size"));
+        }
+
+        let memory = r.take(memory_size)?.to_vec();
+        let reg = r.array()?;
+        let flags = r.array()?;
+        let index = r.u32()? as usize;
+        let counter = r.u32()? as usize;
+
+        let stack_len = r.u16()? as usize;
+        if stack_len > quirks.stack_depth {
+            return Err(StateError::Invalid("stack"));
+        }
+
+        let stack = (0..stack_len)
+            .map(|_| Ok(r.u32()? as usize))
+            .collect::<Result<_, StateError>>()?;
+
+        let [timer, s_timer, vblank, halted] = r.array()?;
+        let audio_pattern = r.array()?;
+        let pitch = r.u8()?;
+
+        let [hires, selected] = r.array()?;
+        if selected >> PLANES != 0 {
+            return Err(StateError::Invalid("plane selection"));
+        }
+
+        let mut planes = self.display.planes;
+        for plane in &mut planes {
+            for word in plane.as_raw_mut_slice() {
+                *word = r.u64()?;
+            }
+        }
+
+        let rng = r.u64()?;
+
+        let key_wait = match r.u8()? {
+            0 => None,
+            1 => {
+                let pc = r.u32()? as usize;
+                let stale = r.u16()?;
+                let pressed = match r.u8()? {
+                    0xFF => None,
+                    key @ 0..=0xF => Some(key),
+                    _ => return Err(StateError::Invalid("key wait")),
+                };
+
+                Some(KeyWait {
+                    pc,
+                    stale: std::array::from_fn(|key| stale & 1 << key != 0),
+                    pressed,
+                })
+            }
+            _ => return Err(StateError::Invalid("key wait")),
+        };
+
+        if !r.bytes.is_empty() {
+            return Err(StateError::Invalid("length"));
+        }
+
+        self.quirks = quirks;
+        self.memory = memory;
+        self.reg = reg;
+        self.flags = flags;
+        self.index = index;
+        self.counter = counter;
+        self.stack = stack;
+        self.timer = timer;
+        self.s_timer = s_timer;
+        self.vblank = vblank != 0;
+        self.halted = halted != 0;
+        self.audio_pattern = audio_pattern;
+        self.pitch = pitch;
+        self.display.hires = hires != 0;
+        self.display.selected = selected;
+        self.display.planes = planes;
+        self.rng.restore(rng);
+        self.key_wait = key_wait;
+
+        Ok(())
+    }
+
+    /// Writes a save state to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File to write, replacing it if it exists
+    pub fn save_state_file(&self, path: impl AsRef<Path>) -> Result<(), StateError> {
+        fs::write(path, self.save_state())?;
+
+        Ok(())
+    }
+
+    /// Loads a save state from a file, in the same way as [`Emulator::load_state`]
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File written by [`Emulator::save_state_file`]
+    pub fn load_state_file(&mut self, path: impl AsRef<Path>) -> Result<(), StateError> {
+        let state = fs::read(path)?;
+
+        self.load_state(&state)
+    }
+}
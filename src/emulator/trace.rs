@@ -0,0 +1,167 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::{decode, Emulator, Instruction, StepInfo};
+
+// the first bytes of a binary trace, followed by a version byte
+const MAGIC: &[u8; 4] = b"C8TR";
+const BINARY_VERSION: u8 = 2;
+
+/// How each executed instruction is written to a trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraceFormat {
+    /// One line per instruction, for reading and diffing:
+    ///
+    /// `0000000001 0200 1202 JP 0x202             00 00 ... 00 I=0000`
+    ///
+    /// with the cycle, PC and opcode, the mnemonic padded to 20 characters,
+    /// then V0 to VF and I after executing. F000 NNNN is written as `LD I, 0xNNNN`
+    #[default]
+    Text,
+    /// A header of `C8TR` and a version byte, then 32 bytes per instruction:
+    /// the cycle as a u64, PC, opcode, NNNN as a u16 (0 for anything but F000 NNNN),
+    /// V0 to VF and I as a u16, all big endian
+    Binary,
+}
+
+impl TraceFormat {
+    /// Gets a format by name
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Either `text` or `binary`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Some(Self::Text),
+            "binary" => Some(Self::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// Writes a record of every executed instruction
+pub struct Trace {
+    out: BufWriter<Box<dyn Write + Send>>, // where records are written
+    format: TraceFormat,                   // how records are written
+    cycle: u64,                            // instructions executed so far
+    error: Option<io::Error>,              // the first write error, tracing stops after one
+}
+
+impl Trace {
+    /// Starts a trace, writing the header if the format has one
+    ///
+    /// # Arguments
+    ///
+    /// * `out` - Where to write the trace
+    /// * `format` - How to write each instruction
+    pub fn new(out: impl Write + Send + 'static, format: TraceFormat) -> Self {
+        let mut trace = Self {
+            out: BufWriter::new(Box::new(out)),
+            format,
+            cycle: 0,
+            error: None,
+        };
+
+        if format == TraceFormat::Binary {
+            let result = trace
+                .out
+                .write_all(MAGIC)
+                .and_then(|_| trace.out.write_all(&[BINARY_VERSION]));
+
+            trace.error = result.err();
+        }
+
+        trace
+    }
+
+    /// Starts a trace in a new file, replacing it if it exists
+    pub fn create(path: impl AsRef<Path>, format: TraceFormat) -> io::Result<Self> {
+        Ok(Self::new(File::create(path)?, format))
+    }
+
+    /// Flushes the trace, returning the first error that happened while writing it
+    pub fn finish(mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        self.out.flush()
+    }
+
+    // writes the record for an instruction the emulator just executed, along with the word
+    // after it if it is F000 NNNN
+    fn record(&mut self, step: &StepInfo, nnnn: Option<u16>, reg: &[u8; 16], index: usize) {
+        if self.error.is_some() {
+            return;
+        }
+
+        self.cycle += 1;
+
+        let result = match self.format {
+            TraceFormat::Text => self.write_text(step, nnnn, reg, index),
+            TraceFormat::Binary => self.write_binary(step, nnnn, reg, index),
+        };
+
+        self.error = result.err();
+    }
+
+    fn write_text(
+        &mut self,
+        step: &StepInfo,
+        nnnn: Option<u16>,
+        reg: &[u8; 16],
+        index: usize,
+    ) -> io::Result<()> {
+        let mnemonic = match (decode(step.opcode), nnnn) {
+            (Ok(Instruction::LongIndex), Some(nnnn)) => format!("LD I, 0x{nnnn:04X}"),
+            (Ok(instruction), _) => instruction.to_string(),
+            (Err(_), _) => "???".to_string(),
+        };
+
+        write!(
+            self.out,
+            "{:010} {:04X} {:04X} {mnemonic:<20}",
+            self.cycle, step.pc_before, step.opcode
+        )?;
+
+        for value in reg {
+            write!(self.out, " {value:02X}")?;
+        }
+
+        writeln!(self.out, " I={index:04X}")
+    }
+
+    fn write_binary(
+        &mut self,
+        step: &StepInfo,
+        nnnn: Option<u16>,
+        reg: &[u8; 16],
+        index: usize,
+    ) -> io::Result<()> {
+        self.out.write_all(&self.cycle.to_be_bytes())?;
+        self.out.write_all(&(step.pc_before as u16).to_be_bytes())?;
+        self.out.write_all(&step.opcode.to_be_bytes())?;
+        self.out.write_all(&nnnn.unwrap_or(0).to_be_bytes())?;
+        self.out.write_all(reg)?;
+        self.out.write_all(&(index as u16).to_be_bytes())
+    }
+}
+
+impl Emulator {
+    // adds an executed instruction to the trace, if there is one
+    pub(super) fn trace_step(&mut self, step: &StepInfo) {
+        if self.trace.is_none() {
+            return;
+        }
+
+        let nnnn = match decode(step.opcode) {
+            Ok(Instruction::LongIndex) => self.peek_word(step.pc_before + 2),
+            _ => None,
+        };
+
+        if let Some(trace) = &mut self.trace {
+            trace.record(step, nnnn, &self.reg, self.index);
+        }
+    }
+}
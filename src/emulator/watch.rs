@@ -0,0 +1,72 @@
+use std::fmt;
+
+use super::Emulator;
+
+/// The kinds of memory access a watchpoint stops on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Any,
+}
+
+/// A range of memory that is watched for accesses by instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub addr: usize,    // first watched address
+    pub len: usize,     // number of watched bytes
+    pub access: Access, // which accesses are caught
+}
+
+/// An access to a watched range, recorded while an instruction executes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub watchpoint: Watchpoint, // the watchpoint that caught the access
+    pub addr: usize,            // first watched address that was accessed
+    pub write: bool,            // whether the access was a write
+    pub pc: usize,              // address of the instruction that made the access
+}
+
+impl fmt::Display for Watchpoint {
+    /// Writes the watchpoint as it is given to the debugger, such as `rwatch 0x300 2`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command = match self.access {
+            Access::Read => "rwatch",
+            Access::Write => "watch",
+            Access::Any => "awatch",
+        };
+
+        write!(f, "{command} 0x{:03X}", self.addr)?;
+
+        if self.len != 1 {
+            write!(f, " {}", self.len)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Emulator {
+    // records a hit for every watchpoint that overlaps an access by the current instruction
+    pub(super) fn check_watchpoints(&mut self, addr: usize, len: usize, write: bool) {
+        for watchpoint in &self.watchpoints {
+            let caught = match watchpoint.access {
+                Access::Read => !write,
+                Access::Write => write,
+                Access::Any => true,
+            };
+
+            let overlaps = addr < watchpoint.addr.saturating_add(watchpoint.len)
+                && watchpoint.addr < addr.saturating_add(len);
+
+            if caught && overlaps {
+                self.watch_hits.push(WatchHit {
+                    watchpoint: *watchpoint,
+                    addr: addr.max(watchpoint.addr),
+                    write,
+                    pc: self.counter.wrapping_sub(2),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+mod keypad;
+mod movie;
+mod network;
+mod pacer;
+#[cfg(feature = "window")]
+mod window;
+
+pub use keypad::{parse_keys, Keypad, ScriptedKeypad};
+pub use movie::{Movie, MovieError, Playback, Recorder, MOVIE_VERSION};
+pub use network::NetworkKeypad;
+pub use pacer::Pacer;
+#[cfg(feature = "window")]
+pub use window::{KeyMap, KeyMapError, WindowFrontend, WindowKeypad};
+
+use crate::emulator::EmuDisplay;
+
+/// Colors for each pixel value as 0RGB, the last two are only used by XO-CHIP programs
+/// drawing on both planes
+pub const PALETTE: [u32; 4] = [0x00_00_00_00, 0x00_FF_FF_FF, 0x00_AA_AA_AA, 0x00_55_55_55];
+
+/// Requests from the user that are handled by the emulator instead of the program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    SaveState(u8), // saves the machine to the numbered slot
+    LoadState(u8), // restores the machine from the numbered slot
+    Rewind,        // steps back one frame, given every frame while the rewind key is held
+}
+
+/// Anything that can show the display to the user, keys are read from a [`Keypad`] instead
+pub trait Frontend {
+    /// Draws the current contents of the display
+    fn refresh(&mut self, display: &EmuDisplay);
+
+    /// Gets the commands the user has given since the last frame
+    fn commands(&mut self) -> Vec<Command> {
+        Vec::new()
+    }
+
+    /// Whether the emulator should keep running
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    /// Waits until the next frame should start, called after every frame
+    ///
+    /// Frontends that don't wait run the program as fast as possible
+    fn wait_frame(&mut self) {}
+}
+
+/// A frontend with no window, for running without a display server
+#[derive(Default)]
+pub struct Headless {
+    pub pacer: Option<Pacer>, // keeps to real time when set, otherwise runs as fast as possible
+}
+
+impl Headless {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a headless frontend that runs at 60 frames per second
+    pub fn paced() -> Self {
+        Self {
+            pacer: Some(Pacer::default()),
+        }
+    }
+}
+
+impl Frontend for Headless {
+    fn refresh(&mut self, _display: &EmuDisplay) {}
+
+    fn wait_frame(&mut self) {
+        if let Some(pacer) = &mut self.pacer {
+            pacer.wait();
+        }
+    }
+}
@@ -0,0 +1,133 @@
+/// Anything the emulator can read the 16 keys from
+///
+/// The emulator takes a [`Keypad::snapshot`] at the start of every frame, so
+/// programs see the same keys for the whole frame
+pub trait Keypad {
+    /// Moves on to the next frame, called once before each snapshot
+    fn update(&mut self) {}
+
+    /// Gets the state of all 16 keys, indexed by key code
+    fn snapshot(&self) -> [bool; 16];
+
+    /// Whether the input has run out, which stops the emulator
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+/// A fixed set of held keys, which never changes
+impl Keypad for [bool; 16] {
+    fn snapshot(&self) -> [bool; 16] {
+        *self
+    }
+}
+
+impl<K: Keypad + ?Sized> Keypad for Box<K> {
+    fn update(&mut self) {
+        (**self).update();
+    }
+
+    fn snapshot(&self) -> [bool; 16] {
+        (**self).snapshot()
+    }
+
+    fn is_finished(&self) -> bool {
+        (**self).is_finished()
+    }
+}
+
+/// Keys that change on set frames, read from a script
+///
+/// Each line of a script is the frame a change happens on, then the keys held
+/// from then on in hex, or `-` for none. Blank lines and lines starting with `#`
+/// are skipped:
+///
+/// ```text
+/// # hold 5 and A for half a second
+/// 10 5a
+/// 40 -
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptedKeypad {
+    changes: Vec<(u64, [bool; 16])>, // frames keys change on and the keys held after, in order
+    frame: u64,                      // the frame about to start
+    held: [bool; 16],                // keys held during the current frame
+}
+
+impl ScriptedKeypad {
+    /// Creates a keypad from a list of changes
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - Frames keys change on with the keys held from then on, in any order
+    pub fn new(mut changes: Vec<(u64, [bool; 16])>) -> Self {
+        changes.sort_by_key(|(frame, _)| *frame);
+
+        Self {
+            changes,
+            ..Self::default()
+        }
+    }
+
+    /// Parses a script, returning a message naming the line if any of it is invalid
+    pub fn parse(script: &str) -> Result<Self, String> {
+        let mut changes = Vec::new();
+
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let change = line
+                .split_once(' ')
+                .and_then(|(frame, held)| Some((frame.parse().ok()?, parse_keys(held.trim())?)));
+
+            match change {
+                Some(change) => changes.push(change),
+                None => return Err(format!("line {}: invalid key change `{line}`", number + 1)),
+            }
+        }
+
+        Ok(Self::new(changes))
+    }
+}
+
+impl Keypad for ScriptedKeypad {
+    fn update(&mut self) {
+        // the last change at or before this frame wins, so several on one frame are fine
+        let started = self
+            .changes
+            .partition_point(|(start, _)| *start <= self.frame);
+
+        if let Some((_, held)) = started.checked_sub(1).map(|last| self.changes[last]) {
+            self.held = held;
+        }
+
+        self.frame += 1;
+    }
+
+    fn snapshot(&self) -> [bool; 16] {
+        self.held
+    }
+}
+
+/// Parses held keys written in hex, such as `5a`, or `-` for none
+pub fn parse_keys(text: &str) -> Option<[bool; 16]> {
+    let mut keys = [false; 16];
+
+    if text == "-" {
+        return Some(keys);
+    }
+
+    if text.is_empty() {
+        return None;
+    }
+
+    for key in text.chars() {
+        keys[key.to_digit(16)? as usize] = true;
+    }
+
+    Some(keys)
+}
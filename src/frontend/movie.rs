@@ -0,0 +1,245 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use super::Keypad;
+use crate::emulator::{Emulator, Quirks};
+
+// the first bytes of every movie file
+const MAGIC: &[u8; 4] = b"C8MV";
+
+/// The movie layout version, bumped whenever the layout changes
+pub const MOVIE_VERSION: u16 = 2;
+
+/// Everything that can go wrong while loading or playing a movie
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),           // the file couldn't be read or written
+    NotAMovie,               // the data doesn't start with the movie magic
+    UnsupportedVersion(u16), // the movie was saved with a different layout
+    Truncated,               // the data ended before every frame was read
+    Mismatch(&'static str),  // the emulator is set up differently, names the setting
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::NotAMovie => write!(f, "not a movie"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "movie is version {version}, but only version {MOVIE_VERSION} is supported"
+            ),
+            Self::Truncated => write!(f, "movie is truncated"),
+            Self::Mismatch(setting) => {
+                write!(f, "movie was recorded with a different {setting}")
+            }
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// A recording of the keys held on every frame of a run
+///
+/// Along with the seed, the ROM and the emulator settings, this is everything
+/// needed to play the run back exactly, since the emulator counts time in instructions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub seed: u64,               // seed the random number generator started from
+    pub rom_hash: u64,           // hash of the ROM, from Movie::hash_rom
+    pub quirks: Quirks,          // quirks the emulator ran with
+    pub memory_size: usize,      // bytes of memory the emulator had
+    pub cycles_per_frame: u32,   // instructions run on each frame
+    pub frames: Vec<[bool; 16]>, // keys held on each frame, indexed by key code
+}
+
+impl Movie {
+    /// Starts an empty movie
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - Seed the random number generator is given before running
+    /// * `rom` - The ROM being run
+    /// * `emu` - The emulator being recorded, whose settings are stored with the movie
+    pub fn new(seed: u64, rom: &[u8], emu: &Emulator) -> Self {
+        Self {
+            seed,
+            rom_hash: Self::hash_rom(rom),
+            quirks: emu.quirks,
+            memory_size: emu.memory.len(),
+            cycles_per_frame: emu.cycles_per_frame(),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Checks an emulator is set up the way it was when the movie was recorded, since
+    /// playing it back with other settings would give a different run
+    pub fn check(&self, emu: &Emulator) -> Result<(), MovieError> {
+        if emu.quirks != self.quirks {
+            return Err(MovieError::Mismatch("set of quirks"));
+        }
+
+        if emu.memory.len() != self.memory_size {
+            return Err(MovieError::Mismatch("memory size"));
+        }
+
+        if emu.cycles_per_frame() != self.cycles_per_frame {
+            return Err(MovieError::Mismatch("number of instructions per frame"));
+        }
+
+        Ok(())
+    }
+
+    /// Hashes a ROM with 64 bit FNV-1a, to check a movie is played with the ROM it was recorded on
+    pub fn hash_rom(rom: &[u8]) -> u64 {
+        rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+    }
+
+    /// Serializes the movie
+    ///
+    /// The layout is the magic `C8MV`, the version as a u16, the seed and ROM hash as
+    /// u64s, the quirks as they are in save states, the memory size, cycles per frame
+    /// and frame count as u32s, then a u16 per frame with a bit set for each held key,
+    /// all big endian
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+
+        out.extend_from_slice(&MOVIE_VERSION.to_be_bytes());
+        out.extend_from_slice(&self.seed.to_be_bytes());
+        out.extend_from_slice(&self.rom_hash.to_be_bytes());
+        out.extend_from_slice(&self.quirks.to_bytes());
+        out.extend_from_slice(&(self.memory_size as u32).to_be_bytes());
+        out.extend_from_slice(&self.cycles_per_frame.to_be_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_be_bytes());
+
+        for keys in &self.frames {
+            let held = (0..16)
+                .filter(|&key| keys[key])
+                .fold(0u16, |held, key| held | 1 << key);
+
+            out.extend_from_slice(&held.to_be_bytes());
+        }
+
+        out
+    }
+
+    /// Parses a movie written by [`Movie::encode`]
+    pub fn decode(data: &[u8]) -> Result<Self, MovieError> {
+        let Some(rest) = data.strip_prefix(MAGIC.as_slice()) else {
+            return Err(MovieError::NotAMovie);
+        };
+
+        let (header, rest) = rest.split_at_checked(40).ok_or(MovieError::Truncated)?;
+
+        let version = u16::from_be_bytes([header[0], header[1]]);
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let seed = u64::from_be_bytes(header[2..10].try_into().unwrap());
+        let rom_hash = u64::from_be_bytes(header[10..18].try_into().unwrap());
+        let quirks = Quirks::from_bytes(header[18..28].try_into().unwrap());
+        let memory_size = u32::from_be_bytes(header[28..32].try_into().unwrap()) as usize;
+        let cycles_per_frame = u32::from_be_bytes(header[32..36].try_into().unwrap());
+        let count = u32::from_be_bytes(header[36..40].try_into().unwrap()) as usize;
+
+        if rest.len() < count * 2 {
+            return Err(MovieError::Truncated);
+        }
+
+        let frames = rest[..count * 2]
+            .chunks(2)
+            .map(|held| {
+                let held = u16::from_be_bytes([held[0], held[1]]);
+                std::array::from_fn(|key| held & 1 << key != 0)
+            })
+            .collect();
+
+        Ok(Self {
+            seed,
+            rom_hash,
+            quirks,
+            memory_size,
+            cycles_per_frame,
+            frames,
+        })
+    }
+
+    /// Writes the movie to a file, replacing it if it exists
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MovieError> {
+        fs::write(path, self.encode())?;
+
+        Ok(())
+    }
+
+    /// Reads a movie written by [`Movie::save`]
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MovieError> {
+        Self::decode(&fs::read(path)?)
+    }
+}
+
+/// Wraps a keypad, adding the keys it gives on every frame to a movie
+pub struct Recorder<K> {
+    pub keypad: K,    // the keypad keys are read from
+    pub movie: Movie, // the frames recorded so far
+}
+
+impl<K: Keypad> Recorder<K> {
+    pub fn new(keypad: K, movie: Movie) -> Self {
+        Self { keypad, movie }
+    }
+}
+
+impl<K: Keypad> Keypad for Recorder<K> {
+    fn update(&mut self) {
+        self.keypad.update();
+        self.movie.frames.push(self.keypad.snapshot());
+    }
+
+    fn snapshot(&self) -> [bool; 16] {
+        self.keypad.snapshot()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.keypad.is_finished()
+    }
+}
+
+/// Gives the keys from a movie, one frame at a time
+///
+/// Finishes once every frame has been played
+pub struct Playback {
+    pub movie: Movie, // the frames being played
+    frame: usize,     // index of the next frame to play
+}
+
+impl Playback {
+    pub fn new(movie: Movie) -> Self {
+        Self { movie, frame: 0 }
+    }
+}
+
+impl Keypad for Playback {
+    fn update(&mut self) {
+        self.frame += 1;
+    }
+
+    fn snapshot(&self) -> [bool; 16] {
+        // nothing is held before the first frame starts
+        match self.frame.checked_sub(1) {
+            Some(frame) => self.movie.frames.get(frame).copied().unwrap_or_default(),
+            None => [false; 16],
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+}
@@ -0,0 +1,80 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::keypad::{parse_keys, Keypad};
+
+/// Keys controlled over TCP, for bots and remote play
+///
+/// Clients send one line for each change, with the keys held from then on in
+/// hex such as `5a`, or `-` for none. Each line is answered with `ok` or an
+/// error. One client is served at a time, and its keys are released when it
+/// disconnects
+pub struct NetworkKeypad {
+    held: Arc<Mutex<[bool; 16]>>, // keys set by the client, shared with the connection thread
+    snapshot: [bool; 16],         // keys held during the current frame
+    addr: SocketAddr,             // where clients can connect
+}
+
+impl NetworkKeypad {
+    /// Starts listening for a client in the background
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address to listen on, port 0 picks a free one
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let held = Arc::new(Mutex::new([false; 16]));
+
+        let shared = Arc::clone(&held);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // a client hanging up is no reason to stop listening for the next one
+                let _ = serve(stream, &shared);
+                *shared.lock().unwrap() = [false; 16];
+            }
+        });
+
+        Ok(Self {
+            held,
+            snapshot: [false; 16],
+            addr,
+        })
+    }
+
+    /// The address clients can connect to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Keypad for NetworkKeypad {
+    fn update(&mut self) {
+        self.snapshot = *self.held.lock().unwrap();
+    }
+
+    fn snapshot(&self) -> [bool; 16] {
+        self.snapshot
+    }
+}
+
+// reads key changes from a client until it disconnects
+fn serve(stream: TcpStream, held: &Mutex<[bool; 16]>) -> io::Result<()> {
+    let mut out = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+
+        match parse_keys(line.trim()) {
+            Some(keys) => {
+                *held.lock().unwrap() = keys;
+                writeln!(out, "ok")?;
+            }
+            None => writeln!(out, "error: invalid keys `{}`", line.trim())?,
+        }
+    }
+
+    Ok(())
+}
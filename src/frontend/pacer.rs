@@ -0,0 +1,49 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// length of one 60hz frame
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Keeps frames to real time by sleeping until each one is due
+///
+/// The emulator itself only counts instructions, so this is how frontends
+/// make programs run at the speed they were written for
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    period: Duration, // time between frames
+    next: Instant,    // when the next frame is due
+}
+
+impl Pacer {
+    /// Creates a pacer with a custom frame length
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - How long each frame should take
+    pub fn new(period: Duration) -> Self {
+        Self {
+            period,
+            next: Instant::now() + period,
+        }
+    }
+
+    /// Sleeps until the next frame is due
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+
+        if now < self.next {
+            thread::sleep(self.next - now);
+            self.next += self.period;
+        } else {
+            // running behind, such as while the window is dragged, so start over instead of catching up
+            self.next = now + self.period;
+        }
+    }
+}
+
+impl Default for Pacer {
+    /// A pacer running at 60 frames per second
+    fn default() -> Self {
+        Self::new(FRAME)
+    }
+}
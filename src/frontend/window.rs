@@ -0,0 +1,148 @@
+mod keymap;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+pub use keymap::{KeyMap, KeyMapError};
+
+use super::{Command, Frontend, Keypad, Pacer, PALETTE};
+use crate::emulator::EmuDisplay;
+
+// how many window pixels are used for each display pixel
+const SCALE: usize = 4;
+
+// the window title, followed by the selected save state slot
+const TITLE: &str = "Bee Chip-8 :)";
+
+// save state slots are numbered from 0 to 9
+const SLOTS: u8 = 10;
+
+/// Frontend that draws to a minifb window and reads keys from it
+///
+/// F5 saves the machine to the selected slot and F9 loads it back, F6 and F7
+/// select the previous and next slot. Holding backspace plays the program backward
+pub struct WindowFrontend {
+    window: Rc<RefCell<Window>>, // window object for simulating the screen, shared with its keypad
+    slot: u8,                    // save state slot used by F5 and F9
+    pacer: Pacer,                // keeps the program running in real time
+    pub commands: bool, // whether the keys above are handled, movies can't be played back with them
+}
+
+/// Keypad that reads keys from a window, created by [`WindowFrontend::keypad`]
+pub struct WindowKeypad {
+    window: Rc<RefCell<Window>>, // the window keys are read from
+    keymap: KeyMap,              // which host keys press each Chip-8 key
+    held: [bool; 16],            // keys held during the current frame
+}
+
+impl WindowFrontend {
+    pub fn new(display: &EmuDisplay) -> Self {
+        let (width, height) = display.dimensions();
+
+        let mut window = Window::new(
+            &format!("{TITLE} - slot 0"),
+            width * SCALE,
+            height * SCALE,
+            WindowOptions::default(),
+        )
+        .unwrap();
+
+        // frames are paced by `wait_frame`, so updates shouldn't wait as well
+        window.limit_update_rate(None);
+
+        Self {
+            window: Rc::new(RefCell::new(window)),
+            slot: 0,
+            pacer: Pacer::default(),
+            commands: true,
+        }
+    }
+
+    /// Creates a keypad reading keys from this window
+    ///
+    /// # Arguments
+    ///
+    /// * `keymap` - Which host keys press each Chip-8 key
+    pub fn keypad(&self, keymap: KeyMap) -> WindowKeypad {
+        WindowKeypad {
+            window: Rc::clone(&self.window),
+            keymap,
+            held: [false; 16],
+        }
+    }
+
+    // changes the save state slot and shows it in the title
+    fn select_slot(&mut self, slot: u8) {
+        self.slot = slot;
+        self.window
+            .borrow_mut()
+            .set_title(&format!("{TITLE} - slot {slot}"));
+    }
+}
+
+impl Frontend for WindowFrontend {
+    fn refresh(&mut self, display: &EmuDisplay) {
+        let (width, height) = display.dimensions();
+        let output: Vec<u32> = display
+            .pixels()
+            .map(|color| PALETTE[color as usize])
+            .collect();
+
+        self.window
+            .borrow_mut()
+            .update_with_buffer(&output[0..], width, height)
+            .unwrap();
+    }
+
+    fn commands(&mut self) -> Vec<Command> {
+        let mut commands = Vec::new();
+
+        if !self.commands {
+            return commands;
+        }
+
+        if self.window.borrow().is_key_down(Key::Backspace) {
+            commands.push(Command::Rewind);
+        }
+
+        let pressed = self.window.borrow().get_keys_pressed(KeyRepeat::No);
+
+        for key in pressed {
+            match key {
+                Key::F5 => commands.push(Command::SaveState(self.slot)),
+                Key::F9 => commands.push(Command::LoadState(self.slot)),
+                Key::F6 => self.select_slot((self.slot + SLOTS - 1) % SLOTS),
+                Key::F7 => self.select_slot((self.slot + 1) % SLOTS),
+                _ => {}
+            }
+        }
+
+        commands
+    }
+
+    fn is_open(&self) -> bool {
+        self.window.borrow().is_open()
+    }
+
+    fn wait_frame(&mut self) {
+        self.pacer.wait();
+    }
+}
+
+impl Keypad for WindowKeypad {
+    fn update(&mut self) {
+        self.held = [false; 16];
+
+        for key in self.window.borrow().get_keys() {
+            if let Some(code) = self.keymap.code(key) {
+                self.held[code as usize] = true;
+            }
+        }
+    }
+
+    fn snapshot(&self) -> [bool; 16] {
+        self.held
+    }
+}
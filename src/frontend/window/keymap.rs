@@ -0,0 +1,323 @@
+use std::fmt;
+
+use minifb::Key;
+
+// keys the window uses for commands, which can't also be Chip-8 keys
+const RESERVED: [Key; 5] = [Key::F5, Key::F6, Key::F7, Key::F9, Key::Backspace];
+
+// names host keys are written as in key maps
+#[rustfmt::skip]
+const NAMES: [(&str, Key); 101] = [
+    ("0", Key::Key0), ("1", Key::Key1), ("2", Key::Key2), ("3", Key::Key3), ("4", Key::Key4),
+    ("5", Key::Key5), ("6", Key::Key6), ("7", Key::Key7), ("8", Key::Key8), ("9", Key::Key9),
+    ("a", Key::A), ("b", Key::B), ("c", Key::C), ("d", Key::D), ("e", Key::E), ("f", Key::F),
+    ("g", Key::G), ("h", Key::H), ("i", Key::I), ("j", Key::J), ("k", Key::K), ("l", Key::L),
+    ("m", Key::M), ("n", Key::N), ("o", Key::O), ("p", Key::P), ("q", Key::Q), ("r", Key::R),
+    ("s", Key::S), ("t", Key::T), ("u", Key::U), ("v", Key::V), ("w", Key::W), ("x", Key::X),
+    ("y", Key::Y), ("z", Key::Z),
+    ("f1", Key::F1), ("f2", Key::F2), ("f3", Key::F3), ("f4", Key::F4), ("f5", Key::F5),
+    ("f6", Key::F6), ("f7", Key::F7), ("f8", Key::F8), ("f9", Key::F9), ("f10", Key::F10),
+    ("f11", Key::F11), ("f12", Key::F12), ("f13", Key::F13), ("f14", Key::F14), ("f15", Key::F15),
+    ("up", Key::Up), ("down", Key::Down), ("left", Key::Left), ("right", Key::Right),
+    ("apostrophe", Key::Apostrophe), ("backquote", Key::Backquote), ("backslash", Key::Backslash),
+    ("comma", Key::Comma), ("equal", Key::Equal), ("leftbracket", Key::LeftBracket),
+    ("minus", Key::Minus), ("period", Key::Period), ("rightbracket", Key::RightBracket),
+    ("semicolon", Key::Semicolon), ("slash", Key::Slash),
+    ("backspace", Key::Backspace), ("delete", Key::Delete), ("end", Key::End),
+    ("enter", Key::Enter), ("escape", Key::Escape), ("home", Key::Home), ("insert", Key::Insert),
+    ("menu", Key::Menu), ("pagedown", Key::PageDown), ("pageup", Key::PageUp),
+    ("pause", Key::Pause), ("space", Key::Space), ("tab", Key::Tab),
+    ("leftshift", Key::LeftShift), ("rightshift", Key::RightShift),
+    ("leftctrl", Key::LeftCtrl), ("rightctrl", Key::RightCtrl),
+    ("leftalt", Key::LeftAlt), ("rightalt", Key::RightAlt),
+    ("numpad0", Key::NumPad0), ("numpad1", Key::NumPad1), ("numpad2", Key::NumPad2),
+    ("numpad3", Key::NumPad3), ("numpad4", Key::NumPad4), ("numpad5", Key::NumPad5),
+    ("numpad6", Key::NumPad6), ("numpad7", Key::NumPad7), ("numpad8", Key::NumPad8),
+    ("numpad9", Key::NumPad9), ("numpaddot", Key::NumPadDot), ("numpadslash", Key::NumPadSlash),
+    ("numpadasterisk", Key::NumPadAsterisk), ("numpadminus", Key::NumPadMinus),
+    ("numpadplus", Key::NumPadPlus), ("numpadenter", Key::NumPadEnter),
+];
+
+/// A problem with a key map, along with the line it's on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMapError {
+    pub line: usize,     // line number starting from 1
+    pub message: String, // what's wrong
+}
+
+impl fmt::Display for KeyMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for KeyMapError {}
+
+/// Which host keys press each of the 16 Chip-8 keys
+///
+/// Key maps are INI files. Keys in the `[keys]` section apply to every ROM, and
+/// keys in a `[rom <hash>]` section only apply to the ROM with that 64 bit FNV-1a
+/// hash in hex, replacing the global ones and taking any host keys they use away
+/// from them. Each line maps a Chip-8 key to one or more host keys separated by
+/// commas. Keys that aren't listed stay on the default QWERTY layout, except for
+/// host keys that were given to another key:
+///
+/// ```ini
+/// [keys]
+/// 5 = z, up
+/// 8 = down
+///
+/// [rom 9c5e3157dda54fc2]
+/// 6 = space
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: [Vec<Key>; 16], // host keys for each Chip-8 key, indexed by key code
+}
+
+// the host keys written for a Chip-8 key, along with the line they were written on
+type Mapping = Option<(usize, Vec<Key>)>;
+
+// which part of a key map the lines being read belong to
+enum Section {
+    None,      // before the first section header
+    Global,    // [keys]
+    Rom(bool), // [rom <hash>], whether it's for the ROM being run
+}
+
+impl KeyMap {
+    /// Reads a key map, checking every section including ones for other ROMs
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Contents of the key map file
+    /// * `rom_hash` - Hash of the ROM being run, as given by `Movie::hash_rom`
+    pub fn parse(config: &str, rom_hash: u64) -> Result<Self, KeyMapError> {
+        let mut global: [Mapping; 16] = Default::default();
+        let mut rom: [Mapping; 16] = Default::default();
+        let mut section = Section::None;
+
+        for (number, line) in config.lines().enumerate() {
+            let error = |message: String| KeyMapError {
+                line: number + 1,
+                message,
+            };
+
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let header = header
+                    .strip_suffix(']')
+                    .ok_or_else(|| error("section header is missing `]`".to_string()))?;
+
+                section = parse_section(header.trim(), rom_hash).ok_or_else(|| {
+                    error(format!(
+                        "unknown section `{header}`, expected `keys` or `rom <hash>`"
+                    ))
+                })?;
+
+                continue;
+            }
+
+            let (code, host) = parse_mapping(line).map_err(error)?;
+
+            match section {
+                Section::None => return Err(error("key outside of a section".to_string())),
+                Section::Global => global[code] = Some((number + 1, host)),
+                Section::Rom(true) => rom[code] = Some((number + 1, host)),
+                Section::Rom(false) => {}
+            }
+        }
+
+        check(&global)?;
+        check(&rom)?;
+
+        Ok(Self::with_written(merge(global, rom)))
+    }
+
+    // puts keys written in a key map over the default layout, taking any host keys they use
+    // away from the keys that weren't written
+    fn with_written(written: [Mapping; 16]) -> Self {
+        let taken: Vec<Key> = written
+            .iter()
+            .flatten()
+            .flat_map(|(_, host)| host.iter().copied())
+            .collect();
+
+        let mut keymap = Self::default();
+
+        for (host, mapping) in keymap.keys.iter_mut().zip(written) {
+            match mapping {
+                Some((_, keys)) => *host = keys,
+                None => host.retain(|key| !taken.contains(key)),
+            }
+        }
+
+        keymap
+    }
+
+    /// The Chip-8 key a host key presses, if any
+    pub fn code(&self, key: Key) -> Option<u8> {
+        let code = self.keys.iter().position(|host| host.contains(&key))?;
+
+        Some(code as u8)
+    }
+
+    /// The host keys that press a Chip-8 key
+    pub fn host_keys(&self, code: u8) -> &[Key] {
+        self.keys.get(code as usize).map_or(&[], Vec::as_slice)
+    }
+}
+
+// puts the keys written for the ROM being run over the global ones, taking any host keys they
+// use away from the global keys that are left
+fn merge(global: [Mapping; 16], rom: [Mapping; 16]) -> [Mapping; 16] {
+    let taken: Vec<Key> = rom
+        .iter()
+        .flatten()
+        .flat_map(|(_, host)| host.iter().copied())
+        .collect();
+
+    let mut written = global;
+
+    for (mapping, rom) in written.iter_mut().zip(rom) {
+        match rom {
+            Some(_) => *mapping = rom,
+            None => {
+                if let Some((_, host)) = mapping {
+                    host.retain(|key| !taken.contains(key));
+                }
+            }
+        }
+    }
+
+    written
+}
+
+// makes sure no host key is written for two Chip-8 keys or is needed for a command, reporting
+// a clash on the later of the two lines
+fn check(written: &[Mapping; 16]) -> Result<(), KeyMapError> {
+    let mut entries: Vec<(usize, usize, &[Key])> = written
+        .iter()
+        .enumerate()
+        .filter_map(|(code, mapping)| {
+            let (line, host) = mapping.as_ref()?;
+            Some((*line, code, host.as_slice()))
+        })
+        .collect();
+
+    entries.sort_by_key(|(line, _, _)| *line);
+
+    let mut used: Vec<(Key, usize)> = Vec::new();
+
+    for (line, code, host) in entries {
+        for &key in host {
+            let error = |message: String| KeyMapError { line, message };
+
+            if RESERVED.contains(&key) {
+                return Err(error(format!(
+                    "`{}` can't be used for key {code:X}, since the window uses it for commands",
+                    key_name(key)
+                )));
+            }
+
+            if let Some((_, other)) = used.iter().find(|(used, _)| *used == key) {
+                return Err(error(format!(
+                    "`{}` is used for both key {other:X} and key {code:X}",
+                    key_name(key)
+                )));
+            }
+
+            used.push((key, code));
+        }
+    }
+
+    Ok(())
+}
+
+impl Default for KeyMap {
+    /// The QWERTY layout, with 1234, QWER, ASDF and ZXCV arranged like the COSMAC VIP keypad
+    fn default() -> Self {
+        let layout = [
+            Key::X,
+            Key::Key1,
+            Key::Key2,
+            Key::Key3,
+            Key::Q,
+            Key::W,
+            Key::E,
+            Key::A,
+            Key::S,
+            Key::D,
+            Key::Z,
+            Key::C,
+            Key::Key4,
+            Key::R,
+            Key::F,
+            Key::V,
+        ];
+
+        Self {
+            keys: layout.map(|key| vec![key]),
+        }
+    }
+}
+
+// reads `keys` or `rom <hash>`, noting whether the ROM section is for the ROM being run
+fn parse_section(header: &str, rom_hash: u64) -> Option<Section> {
+    if header.eq_ignore_ascii_case("keys") {
+        return Some(Section::Global);
+    }
+
+    let (name, hash) = header.split_once(char::is_whitespace)?;
+    let hash = hash.trim();
+
+    if !name.eq_ignore_ascii_case("rom") || hash.len() != 16 {
+        return None;
+    }
+
+    let hash = u64::from_str_radix(hash, 16).ok()?;
+
+    Some(Section::Rom(hash == rom_hash))
+}
+
+// reads a line like `5 = w, up`
+fn parse_mapping(line: &str) -> Result<(usize, Vec<Key>), String> {
+    let (code, host) = line
+        .split_once('=')
+        .ok_or_else(|| format!("expected `<key> = <host keys>`, found `{line}`"))?;
+
+    let code = code.trim();
+    let code = match u8::from_str_radix(code, 16) {
+        Ok(value) if value < 16 && code.len() == 1 => value as usize,
+        _ => return Err(format!("`{code}` is not a Chip-8 key, expected 0 to F")),
+    };
+
+    let mut keys = Vec::new();
+
+    for name in host.split(',').map(str::trim) {
+        let key = NAMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, key)| *key)
+            .ok_or_else(|| format!("unknown host key `{name}`"))?;
+
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    Ok((code, keys))
+}
+
+// the name a host key is written as
+fn key_name(key: Key) -> &'static str {
+    NAMES
+        .iter()
+        .find(|(_, known)| *known == key)
+        .map_or("?", |(name, _)| name)
+}
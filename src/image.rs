@@ -0,0 +1,283 @@
+//! Encodes the display as an image file
+//!
+//! PNG keeps every XO-CHIP color, PBM and ASCII art only show whether each
+//! pixel is lit, which is all plain Chip-8 programs use
+
+use std::path::Path;
+
+use crate::emulator::EmuDisplay;
+use crate::frontend::PALETTE;
+
+/// Characters used for each color in ASCII art, the last two are only used by XO-CHIP programs
+/// drawing on both planes
+pub const ASCII: [char; 4] = ['.', '#', '+', '@'];
+
+/// The formats the display can be written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,   // an indexed color PNG with the window's palette
+    Pbm,   // a plain text PBM, lit pixels are black
+    Ascii, // one line per row, `.` for unlit pixels and `#` for lit ones
+}
+
+impl ImageFormat {
+    /// Picks a format from a file extension, `png`, `pbm`, or `txt` for ASCII art
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "png" => Some(Self::Png),
+            "pbm" => Some(Self::Pbm),
+            "txt" => Some(Self::Ascii),
+            _ => None,
+        }
+    }
+
+    /// Encodes the current contents of the display
+    pub fn encode(&self, display: &EmuDisplay) -> Vec<u8> {
+        match self {
+            Self::Png => {
+                let (width, height) = display.dimensions();
+                let pixels: Vec<u8> = display.pixels().collect();
+
+                encode_png(width, height, &pixels, &PALETTE)
+            }
+            Self::Pbm => pbm(display),
+            Self::Ascii => ascii(display),
+        }
+    }
+}
+
+fn ascii(display: &EmuDisplay) -> Vec<u8> {
+    let (width, _) = display.dimensions();
+    let pixels: Vec<u8> = display.pixels().collect();
+    let mut out = String::new();
+
+    for row in pixels.chunks(width) {
+        out.extend(row.iter().map(|&color| ASCII[color as usize]));
+        out.push('\n');
+    }
+
+    out.into_bytes()
+}
+
+fn pbm(display: &EmuDisplay) -> Vec<u8> {
+    let (width, height) = display.dimensions();
+    let pixels: Vec<u8> = display.pixels().collect();
+    let mut out = format!("P1\n{width} {height}\n");
+
+    for row in pixels.chunks(width) {
+        let row: Vec<&str> = row
+            .iter()
+            .map(|&color| if color == 0 { "0" } else { "1" })
+            .collect();
+
+        out.push_str(&row.join(" "));
+        out.push('\n');
+    }
+
+    out.into_bytes()
+}
+
+/// Encodes an indexed color PNG
+///
+/// # Arguments
+///
+/// * `width` - The width of the image in pixels
+/// * `height` - The height of the image in pixels
+/// * `pixels` - An index into `palette` for each pixel, row by row
+/// * `palette` - Up to 256 colors as 0RGB
+pub fn encode_png(width: usize, height: usize, pixels: &[u8], palette: &[u32]) -> Vec<u8> {
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+
+    // 8 bit indexed color, no interlacing
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+    png_chunk(&mut out, b"IHDR", &header);
+
+    let palette: Vec<u8> = palette
+        .iter()
+        .flat_map(|color| color.to_be_bytes()[1..].to_vec())
+        .collect();
+    png_chunk(&mut out, b"PLTE", &palette);
+
+    // each row starts with filter type 0, meaning no filtering
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+
+    png_chunk(&mut out, b"IEND", &[]);
+
+    out
+}
+
+// writes a PNG chunk with its length and checksum
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+
+    let crc = crc32(kind.iter().chain(data));
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+// wraps data in a zlib stream without compressing it, the display is small enough not to matter
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xFFFF).peekable();
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+
+        out.push(last as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+
+    out
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a lores display with a lit pixel at (1, 0), and one lit on both planes at (0, 1)
+    fn display() -> EmuDisplay {
+        let mut display = EmuDisplay::new("chip8");
+
+        display.draw(&[0x40], (0, 0), true);
+        display.selected = 0b11;
+        display.draw(&[0x00, 0x80, 0x00, 0x80], (0, 0), true);
+
+        display
+    }
+
+    #[test]
+    fn formats_by_extension() {
+        assert_eq!(ImageFormat::from_path("out.PNG"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("a/b.pbm"), Some(ImageFormat::Pbm));
+        assert_eq!(
+            ImageFormat::from_path("frame.txt"),
+            Some(ImageFormat::Ascii)
+        );
+        assert_eq!(ImageFormat::from_path("frame.gif"), None);
+        assert_eq!(ImageFormat::from_path("frame"), None);
+    }
+
+    #[test]
+    fn ascii_art() {
+        let text = String::from_utf8(ImageFormat::Ascii.encode(&display())).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines.len(), 32);
+        assert_eq!(lines[0], format!(".#{}", ".".repeat(62)));
+        assert_eq!(lines[1], format!("@{}", ".".repeat(63)));
+        assert!(lines[2..].iter().all(|line| *line == ".".repeat(64)));
+    }
+
+    #[test]
+    fn plain_pbm() {
+        let text = String::from_utf8(ImageFormat::Pbm.encode(&display())).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[..2], ["P1", "64 32"]);
+        assert_eq!(lines.len(), 2 + 32);
+        assert_eq!(lines[2], format!("0 1{}", " 0".repeat(62)));
+        assert_eq!(lines[3], format!("1{}", " 0".repeat(63)));
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789".iter()), 0xCBF4_3926);
+        assert_eq!(crc32([].iter()), 0);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+        assert_eq!(adler32(&[]), 1);
+    }
+
+    #[test]
+    fn stored_blocks() {
+        let data: Vec<u8> = (0..0x1_0010).map(|i| i as u8).collect();
+        let stream = zlib_stored(&data);
+
+        // a full block, then a final one with the last 17 bytes
+        assert_eq!(stream[..2], [0x78, 0x01]);
+        assert_eq!(stream[2..7], [0, 0xFF, 0xFF, 0x00, 0x00]);
+
+        let second = 7 + 0xFFFF;
+        assert_eq!(stream[second..second + 5], [1, 17, 0, !17, 0xFF]);
+        assert_eq!(stream[second + 5..second + 5 + 17], data[0xFFFF..]);
+        assert_eq!(stream[stream.len() - 4..], adler32(&data).to_be_bytes());
+    }
+
+    #[test]
+    fn png_chunks() {
+        let png = ImageFormat::Png.encode(&display());
+
+        assert_eq!(png[..8], *b"\x89PNG\r\n\x1a\n");
+
+        // walks the chunks, checking each checksum
+        let mut chunks = Vec::new();
+        let mut at = 8;
+
+        while at < png.len() {
+            let len = u32::from_be_bytes(png[at..at + 4].try_into().unwrap()) as usize;
+            let kind = &png[at + 4..at + 8];
+            let data = &png[at + 8..at + 8 + len];
+            let crc = u32::from_be_bytes(png[at + 8 + len..at + 12 + len].try_into().unwrap());
+
+            assert_eq!(crc, crc32(kind.iter().chain(data)));
+
+            chunks.push((kind.to_vec(), data.to_vec()));
+            at += 12 + len;
+        }
+
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| kind.as_slice()).collect();
+        assert_eq!(kinds, [b"IHDR", b"PLTE", b"IDAT", b"IEND"]);
+
+        assert_eq!(chunks[0].1, [0, 0, 0, 64, 0, 0, 0, 32, 8, 3, 0, 0, 0]);
+        assert_eq!(chunks[1].1.len(), PALETTE.len() * 3);
+
+        // a single stored block holds every row, each starting with filter type 0
+        let idat = &chunks[2].1;
+        let raw = &idat[7..idat.len() - 4];
+
+        assert_eq!(raw.len(), 65 * 32);
+        assert_eq!(raw[..3], [0, 0, 1]);
+        assert_eq!(raw[65..67], [0, 3]);
+    }
+}
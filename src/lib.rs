@@ -0,0 +1,19 @@
+//! A Chip-8 interpreter
+//!
+//! The [`emulator`] module contains the window-free core, which shows its display
+//! on any [`frontend::Frontend`] and reads keys from any [`frontend::Keypad`].
+//! The minifb window frontend is only built with the `window` feature.
+//!
+//! [`disasm`] and [`assembler`] convert between ROMs and source code, and
+//! [`debugger`] runs a ROM one instruction at a time. [`image`] saves the
+//! display as a PNG, PBM or text file.
+
+pub mod assembler;
+pub mod debugger;
+pub mod disasm;
+pub mod emulator;
+pub mod frontend;
+pub mod image;
+
+pub use emulator::{Chip8Error, EmuDisplay, Emulator, StepInfo};
+pub use frontend::Frontend;
@@ -1,15 +1,655 @@
-mod emulator;
+use std::{
+    env,
+    io::{self, BufReader},
+    process,
+    sync::{atomic::Ordering, Arc},
+};
 
-use std::env;
+use chip8::{
+    assembler,
+    debugger::{self, Debugger},
+    disasm,
+    emulator::{
+        OpcodePolicy, Quirks, Rewind, Trace, TraceFormat, XorShift, MEMORY_SIZE,
+        XO_CHIP_MEMORY_SIZE,
+    },
+    frontend::{
+        Frontend, Headless, Keypad, Movie, NetworkKeypad, Playback, Recorder, ScriptedKeypad,
+    },
+    image::ImageFormat,
+    Chip8Error, EmuDisplay, Emulator,
+};
 
-use emulator::{EmuDisplay, Emulator};
+// the usage, given the options and notes that only apply when built with a window
+macro_rules! usage {
+    ($window_options:literal, $window_notes:literal) => {
+        concat!(
+            "usage: chip8 [run] [options] <rom>
+       chip8 debug [options] <rom>
+       chip8 gdb [options] <rom>
+       chip8 disasm <rom>
+       chip8 asm <source> <output>
+       chip8 hash <rom>
+
+options:
+    --quirks <preset>   vip, chip48, schip or xochip (default chip48, but with BNNN jumping
+                        from V0 rather than VX)
+    --memory <bytes>    amount of memory (default 65536 for xochip, otherwise 4096)
+    --unknown-opcodes <policy>
+                        ignore, log or halt on invalid instructions (default ignore)
+    --strict            same as --unknown-opcodes halt
+    --stack-depth <n>   how many subroutine calls can be nested (default from the preset)
+    --stack-wrap        drop the oldest return address instead of overflowing
+    --seed <n>          seed for CXNN, so random programs run the same way every time
+    --rewind <seconds>  how far back holding backspace can go, 0 to disable (default 10)
+    --port <port>       port for gdb to listen on, only on localhost (default 1234)
+    --trace <file>      write every executed instruction to a file
+    --trace-format <format>
+                        text or binary (default text)
+    --headless          run without a window, always the case when built without one
+    --cycles <n>        run this many instructions as fast as possible without a window, then stop
+    --dump-frame <file> save the display when the program stops, as .png, .pbm or .txt
+    --record <file>     record the keys held on every frame into a movie
+    --play <file>       play a movie back instead of reading keys
+    --input-script <file>
+                        read keys from a script of frame numbers and held keys in hex,
+                        which needs --cycles without a window
+    --input-port <port> let a client on localhost send held keys in hex, one line per change
+",
+            $window_options,
+            "
+headless runs with --play or --input-script go as fast as possible",
+            $window_notes
+        )
+    };
+}
+
+#[cfg(feature = "window")]
+const USAGE: &str = usage!(
+    "    --keymap <file>     host keys for each Chip-8 key (default ~/.config/chip8/keys.ini)\n",
+    "\nkey maps can have a section for each ROM, named by the hash from `chip8 hash`"
+);
+
+#[cfg(not(feature = "window"))]
+const USAGE: &str = usage!("", "");
+
+/// Settings for running a ROM, read from the command line
+struct Options {
+    rom: String,
+    quirks: Quirks,
+    memory: usize,
+    unknown_opcodes: OpcodePolicy,
+    seed: Option<u64>,
+    rewind: usize,
+    port: u16,
+    trace: Option<String>,
+    trace_format: TraceFormat,
+    headless: bool,
+    cycles: Option<u64>,
+    dump_frame: Option<(String, ImageFormat)>,
+    record: Option<String>,
+    play: Option<String>,
+    input_script: Option<String>,
+    input_port: Option<u16>,
+    #[cfg(feature = "window")]
+    keymap: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut rom = None;
+        let mut quirks = Quirks::DEFAULT;
+        let mut preset = None;
+        let mut memory = None;
+        let mut unknown_opcodes = OpcodePolicy::Ignore;
+        let mut stack_depth = None;
+        let mut stack_wrap = false;
+        let mut seed = None;
+        let mut rewind = 10;
+        let mut port = 1234;
+        let mut trace = None;
+        let mut trace_format = TraceFormat::Text;
+        let mut headless = false;
+        let mut cycles = None;
+        let mut dump_frame = None;
+        let mut record = None;
+        let mut play = None;
+        let mut input_script = None;
+        let mut input_port = None;
+        #[cfg(feature = "window")]
+        let mut keymap = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--quirks" => {
+                    let name = args.next().ok_or("--quirks needs a preset name")?;
+                    quirks = Quirks::from_name(name)
+                        .ok_or_else(|| format!("unknown quirks preset `{name}`"))?;
+                    preset = Some(name.to_ascii_lowercase());
+                }
+                "--memory" => {
+                    let size = args.next().ok_or("--memory needs a size")?;
+                    let size = size
+                        .parse()
+                        .ok()
+                        .filter(|size| (MEMORY_SIZE..=XO_CHIP_MEMORY_SIZE).contains(size))
+                        .ok_or_else(|| {
+                            format!(
+                                "invalid memory size `{size}`, expected {MEMORY_SIZE} to {XO_CHIP_MEMORY_SIZE}"
+                            )
+                        })?;
+
+                    memory = Some(size);
+                }
+                "--unknown-opcodes" => {
+                    let name = args.next().ok_or("--unknown-opcodes needs a policy")?;
+                    unknown_opcodes = OpcodePolicy::from_name(name)
+                        .ok_or_else(|| format!("unknown opcode policy `{name}`"))?;
+                }
+                "--strict" => unknown_opcodes = OpcodePolicy::Halt,
+                "--stack-depth" => {
+                    let depth = args.next().ok_or("--stack-depth needs a depth")?;
+                    let depth = depth
+                        .parse()
+                        .ok()
+                        .filter(|&depth| depth > 0)
+                        .ok_or_else(|| format!("invalid stack depth `{depth}`"))?;
+
+                    stack_depth = Some(depth);
+                }
+                "--stack-wrap" => stack_wrap = true,
+                "--seed" => {
+                    let value = args.next().ok_or("--seed needs a number")?;
+
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid seed `{value}`"))?,
+                    );
+                }
+                "--rewind" => {
+                    let seconds = args.next().ok_or("--rewind needs a number of seconds")?;
+
+                    rewind = seconds
+                        .parse()
+                        .map_err(|_| format!("invalid rewind length `{seconds}`"))?;
+                }
+                "--port" => {
+                    let value = args.next().ok_or("--port needs a port number")?;
+
+                    port = value
+                        .parse()
+                        .map_err(|_| format!("invalid port `{value}`"))?;
+                }
+                "--trace" => {
+                    let path = args.next().ok_or("--trace needs a file")?;
+                    trace = Some(path.clone());
+                }
+                "--trace-format" => {
+                    let name = args.next().ok_or("--trace-format needs a format")?;
+                    trace_format = TraceFormat::from_name(name)
+                        .ok_or_else(|| format!("unknown trace format `{name}`"))?;
+                }
+                "--headless" => headless = true,
+                "--cycles" => {
+                    let value = args.next().ok_or("--cycles needs a number")?;
+
+                    cycles = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid cycle count `{value}`"))?,
+                    );
+                }
+                "--dump-frame" => {
+                    let path = args.next().ok_or("--dump-frame needs a file")?;
+                    let format = ImageFormat::from_path(path).ok_or_else(|| {
+                        format!("unknown image format for `{path}`, expected .png, .pbm or .txt")
+                    })?;
+
+                    dump_frame = Some((path.clone(), format));
+                }
+                "--record" => {
+                    let path = args.next().ok_or("--record needs a file")?;
+                    record = Some(path.clone());
+                }
+                "--play" => {
+                    let path = args.next().ok_or("--play needs a file")?;
+                    play = Some(path.clone());
+                }
+                "--input-script" => {
+                    let path = args.next().ok_or("--input-script needs a file")?;
+                    input_script = Some(path.clone());
+                }
+                "--input-port" => {
+                    let value = args.next().ok_or("--input-port needs a port number")?;
+
+                    input_port = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid port `{value}`"))?,
+                    );
+                }
+                #[cfg(feature = "window")]
+                "--keymap" => {
+                    let path = args.next().ok_or("--keymap needs a file")?;
+                    keymap = Some(path.clone());
+                }
+                flag if flag.starts_with("--") => return Err(format!("unknown option `{flag}`")),
+                path => rom = Some(path.to_string()),
+            }
+        }
+
+        if record.is_some() && play.is_some() {
+            return Err("--record and --play can't be used together".to_string());
+        }
+
+        let inputs = [play.is_some(), input_script.is_some(), input_port.is_some()];
+        if inputs.into_iter().filter(|&input| input).count() > 1 {
+            return Err(
+                "only one of --play, --input-script and --input-port can be used".to_string(),
+            );
+        }
+
+        // scripts never run out, so without a window to close nothing would stop the run
+        let windowless = headless || !cfg!(feature = "window");
+        if input_script.is_some() && windowless && cycles.is_none() {
+            return Err("--input-script needs --cycles when running without a window".to_string());
+        }
+
+        quirks.stack_depth = stack_depth.unwrap_or(quirks.stack_depth);
+        quirks.stack_wrap |= stack_wrap;
+
+        // the preset is checked by name, since the flags above can change its quirks
+        let default_memory = if preset.as_deref() == Some("xochip") {
+            XO_CHIP_MEMORY_SIZE
+        } else {
+            MEMORY_SIZE
+        };
+
+        Ok(Self {
+            rom: rom.ok_or("no ROM given")?,
+            quirks,
+            memory: memory.unwrap_or(default_memory),
+            unknown_opcodes,
+            seed,
+            rewind,
+            port,
+            trace,
+            trace_format,
+            headless,
+            cycles,
+            dump_frame,
+            record,
+            play,
+            input_script,
+            input_port,
+            #[cfg(feature = "window")]
+            keymap,
+        })
+    }
+}
 
 fn main() {
-    let path = &env::args().collect::<Vec<String>>()[1];
-    let script = std::fs::read(path).unwrap();
+    let args = env::args().skip(1).collect::<Vec<String>>();
+
+    match args.first().map(String::as_str) {
+        Some("disasm") => {
+            let [path] = &args[1..] else {
+                usage_error("disasm needs a ROM");
+            };
+
+            let script = read_file(path);
+
+            print!("{}", disasm::disassemble(&script));
+        }
+        Some("asm") => {
+            let [source_path, out_path] = &args[1..] else {
+                usage_error("asm needs a source file and an output file");
+            };
+
+            let source = String::from_utf8(read_file(source_path)).unwrap_or_else(|_| {
+                eprintln!("{source_path}: not valid UTF-8");
+                process::exit(1);
+            });
+
+            match assembler::assemble(&source) {
+                Ok(rom) => {
+                    if let Err(err) = std::fs::write(out_path, rom) {
+                        eprintln!("{out_path}: {err}");
+                        process::exit(1);
+                    }
+                }
+                Err(err) => {
+                    eprintln!("{source_path}: {err}");
+                    process::exit(1);
+                }
+            }
+        }
+        Some("hash") => {
+            let [path] = &args[1..] else {
+                usage_error("hash needs a ROM");
+            };
+
+            println!("{:016x}", Movie::hash_rom(&read_file(path)));
+        }
+        Some("run") => run(&args[1..]),
+        Some("debug") => debug(&args[1..]),
+        Some("gdb") => gdb(&args[1..]),
+        _ => run(&args),
+    }
+}
+
+// prints a problem with the command line along with the usage, then exits
+fn usage_error(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    process::exit(2);
+}
+
+// reads a whole file, exiting with a message if it can't be read
+fn read_file(path: &str) -> Vec<u8> {
+    std::fs::read(path).unwrap_or_else(|err| {
+        eprintln!("{path}: {err}");
+        process::exit(1);
+    })
+}
+
+// parses the options and creates an emulator with the ROM loaded, exiting on any problem
+fn load(args: &[String]) -> (Options, Emulator) {
+    let options = Options::parse(args).unwrap_or_else(|err| usage_error(&err));
+
+    let script = read_file(&options.rom);
 
     let display = EmuDisplay::new("chip8");
-    let mut emu = Emulator::new(display, 1428, true);
+    let mut emu = Emulator::new(display, 1428, options.quirks);
+    emu.set_memory_size(options.memory);
+    emu.unknown_opcodes = options.unknown_opcodes;
+    emu.state_path = Some(options.rom.clone().into());
+    emu.log = Some(Box::new(|message| eprintln!("{message}")));
+
+    if let Some(seed) = options.seed {
+        emu.rng = Box::new(XorShift::new(seed));
+    }
+
+    if options.rewind > 0 {
+        emu.rewind = Some(Rewind::new(options.rewind * 60));
+    }
+
+    if let Some(path) = &options.trace {
+        match Trace::create(path, options.trace_format) {
+            Ok(trace) => emu.trace = Some(trace),
+            Err(err) => {
+                eprintln!("{path}: {err}");
+                process::exit(1);
+            }
+        }
+    }
+
+    emu.load_font();
+
+    if let Err(err) = emu.load_script(&script) {
+        eprintln!("{}: {err}", options.rom);
+        process::exit(1);
+    }
+
+    (options, emu)
+}
+
+// flushes the trace, if there is one, exiting if any of it couldn't be written
+fn finish_trace(emu: &mut Emulator, options: &Options) {
+    let (Some(trace), Some(path)) = (emu.trace.take(), &options.trace) else {
+        return;
+    };
+
+    if let Err(err) = trace.finish() {
+        eprintln!("{path}: {err}");
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) {
+    let (options, mut emu) = load(args);
+
+    let (mut frontend, window_keypad) = frontend(&emu, &options);
+    let mut keypad = keypad(&mut emu, &options, window_keypad);
+
+    let result = match &options.record {
+        Some(path) => {
+            // the seed has to be known to be saved, so one is picked here
+            let seed = options.seed.unwrap_or_else(rand::random);
+            emu.rng = Box::new(XorShift::new(seed));
+
+            let movie = Movie::new(seed, &read_file(&options.rom), &emu);
+            let mut recorder = Recorder::new(keypad, movie);
+            let result = run_with(&mut emu, &options, frontend.as_mut(), &mut recorder);
+
+            // saved even when the program crashed, since that's worth playing back
+            if let Err(err) = recorder.movie.save(path) {
+                eprintln!("{path}: {err}");
+                process::exit(1);
+            }
+
+            result
+        }
+        None => run_with(&mut emu, &options, frontend.as_mut(), keypad.as_mut()),
+    };
+    finish_trace(&mut emu, &options);
+
+    // the frame is still worth saving when the program crashed
+    if let Some((path, format)) = &options.dump_frame {
+        if let Err(err) = std::fs::write(path, format.encode(&emu.display)) {
+            eprintln!("{path}: {err}");
+            process::exit(1);
+        }
+    }
+
+    if let Err(err) = result {
+        eprintln!("{}: {err}", options.rom);
+        process::exit(1);
+    }
+}
+
+// runs for the number of cycles asked for, otherwise until the frontend closes
+fn run_with(
+    emu: &mut Emulator,
+    options: &Options,
+    frontend: &mut dyn Frontend,
+    keypad: &mut (impl Keypad + ?Sized),
+) -> Result<(), Chip8Error> {
+    match options.cycles {
+        Some(cycles) => emu.run_for(cycles, keypad),
+        None => emu.main_loop(frontend, keypad),
+    }
+}
+
+fn debug(args: &[String]) {
+    let (options, emu) = load(args);
+    let mut debugger = Debugger::new(emu);
+
+    // ctrl-c stops a running program instead of the debugger
+    let interrupt = Arc::clone(&debugger.interrupt);
+    if let Err(err) = ctrlc::set_handler(move || interrupt.store(true, Ordering::Relaxed)) {
+        eprintln!("ctrl-c won't stop running programs: {err}");
+    }
+
+    let result = debugger.repl(BufReader::new(io::stdin()), io::stdout());
+    finish_trace(&mut debugger.emu, &options);
+
+    if let Err(err) = result {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+}
+
+fn gdb(args: &[String]) {
+    let (options, emu) = load(args);
+    let mut debugger = Debugger::new(emu);
+
+    eprintln!("waiting for gdb on 127.0.0.1:{}", options.port);
+
+    let result = debugger::gdb::listen(&mut debugger, ("127.0.0.1", options.port));
+    finish_trace(&mut debugger.emu, &options);
+
+    if let Err(err) = result {
+        eprintln!("{err}");
+        process::exit(1);
+    }
+}
+
+// picks where keys come from, the window is only used when nothing else was asked for
+fn keypad(
+    emu: &mut Emulator,
+    options: &Options,
+    window: Option<Box<dyn Keypad>>,
+) -> Box<dyn Keypad> {
+    if let Some(path) = &options.play {
+        let movie = Movie::load(path).unwrap_or_else(|err| {
+            eprintln!("{path}: {err}");
+            process::exit(1);
+        });
+
+        if movie.rom_hash != Movie::hash_rom(&read_file(&options.rom)) {
+            eprintln!("{path}: recorded with a different ROM than {}", options.rom);
+            process::exit(1);
+        }
+
+        if let Err(err) = movie.check(emu) {
+            eprintln!("{path}: {err}");
+            process::exit(1);
+        }
+
+        emu.rng = Box::new(XorShift::new(movie.seed));
+
+        return Box::new(Playback::new(movie));
+    }
+
+    if let Some(path) = &options.input_script {
+        let script = String::from_utf8_lossy(&read_file(path)).into_owned();
+        let keypad = ScriptedKeypad::parse(&script).unwrap_or_else(|err| {
+            eprintln!("{path}: {err}");
+            process::exit(1);
+        });
+
+        return Box::new(keypad);
+    }
+
+    if let Some(port) = options.input_port {
+        let keypad = NetworkKeypad::bind(("127.0.0.1", port)).unwrap_or_else(|err| {
+            eprintln!("127.0.0.1:{port}: {err}");
+            process::exit(1);
+        });
+
+        eprintln!("listening for keys on {}", keypad.local_addr());
+
+        return Box::new(keypad);
+    }
+
+    window.unwrap_or_else(|| Box::new([false; 16]))
+}
+
+// a frontend with no window, which only keeps to real time when a person is giving the input
+fn headless(options: &Options) -> (Box<dyn Frontend>, Option<Box<dyn Keypad>>) {
+    let frontend = match (&options.play, &options.input_script) {
+        (None, None) => Headless::paced(),
+        _ => Headless::new(),
+    };
+
+    (Box::new(frontend), None)
+}
+
+// picks where the display is shown, along with the window's keypad if there is one
+fn frontend(emu: &Emulator, options: &Options) -> (Box<dyn Frontend>, Option<Box<dyn Keypad>>) {
+    if options.headless || options.cycles.is_some() {
+        return headless(options);
+    }
+
+    window(emu, options)
+}
+
+#[cfg(feature = "window")]
+fn window(emu: &Emulator, options: &Options) -> (Box<dyn Frontend>, Option<Box<dyn Keypad>>) {
+    // checked before opening the window, so mistakes are reported straight away
+    let keymap = load_keymap(options);
+    let mut window = chip8::frontend::WindowFrontend::new(&emu.display);
+
+    // save states and rewinding would make movies impossible to play back
+    window.commands = options.record.is_none() && options.play.is_none();
+
+    let keypad = window.keypad(keymap);
+
+    (Box::new(window), Some(Box::new(keypad)))
+}
+
+// reads the key map given with --keymap, or the one in the config directory if there is one,
+// exiting if it's invalid
+#[cfg(feature = "window")]
+fn load_keymap(options: &Options) -> chip8::frontend::KeyMap {
+    use chip8::frontend::KeyMap;
+
+    let rom_hash = Movie::hash_rom(&read_file(&options.rom));
+
+    let path = match &options.keymap {
+        Some(path) => path.into(),
+        None => match default_keymap_path() {
+            Some(path) if path.exists() => path,
+            _ => return KeyMap::default(),
+        },
+    };
+
+    let config = String::from_utf8_lossy(&read_file(&path.to_string_lossy())).into_owned();
+
+    KeyMap::parse(&config, rom_hash).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", path.display());
+        process::exit(1);
+    })
+}
+
+// where the key map is looked for when --keymap isn't given
+#[cfg(feature = "window")]
+fn default_keymap_path() -> Option<std::path::PathBuf> {
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => dir.into(),
+        None => std::path::PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    Some(config.join("chip8").join("keys.ini"))
+}
+
+// without a window every run is headless
+#[cfg(not(feature = "window"))]
+fn window(_emu: &Emulator, options: &Options) -> (Box<dyn Frontend>, Option<Box<dyn Keypad>>) {
+    headless(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(String::from).collect();
+
+        Options::parse(&args)
+    }
+
+    #[test]
+    fn memory_follows_the_preset() {
+        let options = parse("--quirks xochip --stack-depth 32 --stack-wrap rom.ch8").unwrap();
+
+        assert_eq!(options.memory, XO_CHIP_MEMORY_SIZE);
+        assert_eq!(options.quirks.stack_depth, 32);
+        assert!(options.quirks.stack_wrap);
 
-    emu.run_script(script);
+        assert_eq!(
+            parse("--quirks XOCHIP rom.ch8").unwrap().memory,
+            XO_CHIP_MEMORY_SIZE
+        );
+        assert_eq!(parse("--quirks schip rom.ch8").unwrap().memory, MEMORY_SIZE);
+        assert_eq!(parse("rom.ch8").unwrap().memory, MEMORY_SIZE);
+        assert_eq!(
+            parse("--quirks xochip --memory 8192 rom.ch8")
+                .unwrap()
+                .memory,
+            8192
+        );
+    }
 }
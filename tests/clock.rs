@@ -0,0 +1,95 @@
+//! Checks that time is counted in instructions, so runs don't depend on the host clock
+
+use std::thread;
+use std::time::Duration;
+
+use chip8::emulator::Quirks;
+use chip8::{EmuDisplay, Frontend};
+
+mod common;
+
+// sets the delay timer, then draws random sprites forever
+const PROGRAM: &str = "
+: main
+  v0 := 200
+  delay := v0
+  i := dot
+  loop
+    v1 := random 0x3F
+    v2 := random 0x1F
+    sprite v1 v2 1
+  again
+: dot
+  0x80
+";
+
+// closes after a number of frames, optionally taking a while over each one
+struct Frames {
+    left: u32,
+    sleep: Duration,
+}
+
+impl Frontend for Frames {
+    fn refresh(&mut self, _display: &EmuDisplay) {}
+
+    fn is_open(&self) -> bool {
+        self.left > 0
+    }
+
+    fn wait_frame(&mut self) {
+        self.left -= 1;
+        thread::sleep(self.sleep);
+    }
+}
+
+#[test]
+fn timers_tick_once_per_frame() {
+    let mut emu = common::emulator(PROGRAM, Quirks::CHIP_48);
+    let frame = emu.cycles_per_frame() as u64;
+
+    // the delay is set during the first frame, which then ticks it once
+    emu.run_for(frame * 7, &mut [false; 16]).unwrap();
+    assert_eq!(emu.timer, 200 - 7);
+
+    // a partial frame doesn't tick
+    emu.run_for(frame - 1, &mut [false; 16]).unwrap();
+    assert_eq!(emu.timer, 200 - 7);
+
+    // and run_cycles leaves ticking to the caller
+    emu.run_cycles(frame as u32 * 3).unwrap();
+    assert_eq!(emu.timer, 200 - 7);
+}
+
+#[test]
+fn independent_of_the_host_clock() {
+    let mut fast = common::emulator(PROGRAM, Quirks::CHIP_48);
+    fast.main_loop(
+        &mut Frames {
+            left: 20,
+            sleep: Duration::ZERO,
+        },
+        &mut [false; 16],
+    )
+    .unwrap();
+
+    let mut slow = common::emulator(PROGRAM, Quirks::CHIP_48);
+    slow.main_loop(
+        &mut Frames {
+            left: 20,
+            sleep: Duration::from_millis(5),
+        },
+        &mut [false; 16],
+    )
+    .unwrap();
+
+    assert_eq!(fast.timer, 200 - 20);
+    assert_eq!(slow.save_state(), fast.save_state());
+
+    // and running the same number of instructions without a frontend ends up the same too
+    let mut direct = common::emulator(PROGRAM, Quirks::CHIP_48);
+    direct
+        .run_for(20 * direct.cycles_per_frame() as u64, &mut [false; 16])
+        .unwrap();
+
+    assert_eq!(direct.save_state(), fast.save_state());
+}
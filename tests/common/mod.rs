@@ -0,0 +1,67 @@
+//! Fixtures shared by the integration tests
+//!
+//! Each test file only uses some of them
+#![allow(dead_code)]
+
+use chip8::assembler;
+use chip8::emulator::{Quirks, XorShift, MEMORY_SIZE};
+use chip8::{EmuDisplay, Emulator};
+
+/// Creates an emulator with 4KiB of memory and the font and a ROM loaded, ready to run
+///
+/// # Arguments
+///
+/// * `rom` - The ROM to load
+/// * `quirks` - The interpreter to emulate
+/// * `seed` - Seed for the random number generator, so runs can be repeated
+pub fn load(rom: &[u8], quirks: Quirks, seed: u64) -> Emulator {
+    load_sized(rom, quirks, seed, MEMORY_SIZE)
+}
+
+/// Creates an emulator in the same way as [`load`], but with `memory` bytes of memory
+pub fn load_sized(rom: &[u8], quirks: Quirks, seed: u64, memory: usize) -> Emulator {
+    let mut emu = Emulator::new(EmuDisplay::new("chip8"), 1428, quirks);
+    emu.set_memory_size(memory);
+    emu.rng = Box::new(XorShift::new(seed));
+    emu.load_font();
+    emu.load_script(rom).unwrap();
+
+    emu
+}
+
+/// Assembles a program and loads it in the same way as [`load`], with a seed of 1
+pub fn emulator(source: &str, quirks: Quirks) -> Emulator {
+    emulator_sized(source, quirks, MEMORY_SIZE)
+}
+
+/// Assembles a program and loads it in the same way as [`load_sized`], with a seed of 1
+pub fn emulator_sized(source: &str, quirks: Quirks, memory: usize) -> Emulator {
+    load_sized(&assembler::assemble(source).unwrap(), quirks, 1, memory)
+}
+
+/// Executes instructions until the program exits, panicking if one fails
+pub fn finish(emu: &mut Emulator) {
+    while !emu.halted {
+        emu.step().unwrap();
+    }
+}
+
+/// Assembles a program and runs it until it exits
+pub fn run(source: &str, quirks: Quirks) -> Emulator {
+    run_sized(source, quirks, MEMORY_SIZE)
+}
+
+/// Assembles a program and runs it until it exits, with `memory` bytes of memory
+pub fn run_sized(source: &str, quirks: Quirks, memory: usize) -> Emulator {
+    let mut emu = emulator_sized(source, quirks, memory);
+    finish(&mut emu);
+
+    emu
+}
+
+/// Gets the color of a pixel, with a bit set for each plane it is lit on
+pub fn pixel(emu: &Emulator, x: usize, y: usize) -> u8 {
+    let (width, _) = emu.display.dimensions();
+
+    emu.display.pixels().nth(y * width + x).unwrap()
+}
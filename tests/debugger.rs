@@ -0,0 +1,155 @@
+//! Drives the debugger's commands the way the REPL does
+
+use std::sync::atomic::Ordering;
+
+use chip8::debugger::{Debugger, Stop, Watch};
+use chip8::emulator::{Access, Quirks, MEMORY_SIZE};
+
+mod common;
+
+fn debugger(source: &str) -> Debugger {
+    Debugger::new(common::emulator(source, Quirks::CHIP_48))
+}
+
+// runs commands, returning everything they wrote
+fn commands(debugger: &mut Debugger, lines: &[&str]) -> String {
+    let mut out = Vec::new();
+
+    for line in lines {
+        debugger.command(line, &mut out).unwrap();
+    }
+
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn keys_can_be_held_from_the_repl() {
+    let mut debugger = debugger(": main v0 := key exit");
+
+    // start waiting, then press 5
+    let out = commands(&mut debugger, &["step 2", "key 5", "key", "continue"]);
+
+    assert!(out.contains("holding 5"), "{out}");
+    assert!(out.contains("program halted"), "{out}");
+    assert_eq!(debugger.emu.reg[0], 5);
+}
+
+#[test]
+fn interrupt_stops_endless_loops() {
+    let mut debugger = debugger(": main loop again");
+
+    debugger.interrupt.store(true, Ordering::Relaxed);
+
+    assert_eq!(debugger.resume(), Stop::Interrupted);
+    assert_eq!(debugger.emu.counter, 0x202);
+}
+
+const WATCHED: &str = "
+: main
+  i := 0x300
+  v0 := 0x80
+  save v0
+  load v0
+  v3 := 4
+  loop
+    v3 += 2
+  again
+";
+
+fn watch(access: Access, args: &str) -> Watch {
+    let args: Vec<&str> = args.split_whitespace().collect();
+
+    Watch::parse(access, &args, MEMORY_SIZE).unwrap()
+}
+
+#[test]
+fn memory_watches() {
+    // a write to 0x300 by the save at 0x206, then a read by the load at 0x208
+    for (access, stop) in [
+        (Access::Write, 0x206),
+        (Access::Read, 0x208),
+        (Access::Any, 0x206),
+    ] {
+        let mut debugger = debugger(WATCHED);
+        let id = debugger.add_watch(watch(access, "0x2FF 2"));
+
+        assert_eq!(
+            debugger.resume(),
+            Stop::Watch { id, pc: stop },
+            "{access:?}"
+        );
+    }
+
+    // watches past the accessed range are never triggered
+    let mut debugger = debugger(WATCHED);
+    debugger.add_watch(watch(Access::Any, "0x301"));
+
+    let stop = debugger.run_until(|emu| emu.reg[3] == 0x10);
+    assert_eq!(stop, Stop::Done);
+}
+
+#[test]
+fn register_watches() {
+    let mut debugger = debugger(WATCHED);
+    let index = debugger.add_watch(watch(Access::Write, "i"));
+    let v3 = debugger.add_watch(watch(Access::Write, "v3 if v3 == 0A"));
+
+    assert_eq!(
+        debugger.resume(),
+        Stop::Watch {
+            id: index,
+            pc: 0x202
+        }
+    );
+    debugger.remove_watch(index);
+
+    // only stops once the condition holds, after 4, 6 and 8
+    assert_eq!(debugger.resume(), Stop::Watch { id: v3, pc: 0x20C });
+    assert_eq!(debugger.emu.reg[3], 0x0A);
+}
+
+#[test]
+fn watch_commands() {
+    let mut debugger = debugger(WATCHED);
+    let out = commands(
+        &mut debugger,
+        &[
+            "watch v3 if v3 >= 10",
+            "rwatch 0x300 2",
+            "watch",
+            "unwatch 1",
+            "continue",
+        ],
+    );
+
+    assert!(out.contains("#1  watch V3 if V3 >= 0x10"), "{out}");
+    assert!(out.contains("#2  rwatch 0x300 2"), "{out}");
+    assert!(
+        out.contains("#2  rwatch 0x300 2 triggered by 0x208"),
+        "{out}"
+    );
+
+    let error = |access, args: &[&str]| Watch::parse(access, args, MEMORY_SIZE).unwrap_err();
+
+    assert_eq!(error(Access::Read, &["v3"]), "invalid address `v3`");
+    assert_eq!(
+        error(Access::Write, &["v3", "if", "v3", "=", "1"]),
+        "invalid comparison `=`"
+    );
+    assert_eq!(error(Access::Write, &["0x300", "0"]), "invalid length `0`");
+    assert_eq!(
+        error(Access::Write, &["ffffffffffffffff", "2"]),
+        "0xFFFFFFFFFFFFFFFF 2 goes past the end of memory at 0x1000"
+    );
+    assert_eq!(
+        error(Access::Read, &["0xfff", "2"]),
+        "0xFFF 2 goes past the end of memory at 0x1000"
+    );
+
+    // the REPL reports it rather than adding the watch
+    let out = commands(&mut debugger, &["watch ffffffffffffffff 2", "watch"]);
+    assert_eq!(
+        out,
+        "0xFFFFFFFFFFFFFFFF 2 goes past the end of memory at 0x1000\n#2  rwatch 0x300 2\n"
+    );
+}
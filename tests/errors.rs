@@ -0,0 +1,201 @@
+//! Checks that bad ROMs stop with an error instead of panicking
+
+use std::sync::{Arc, Mutex};
+
+use chip8::emulator::{OpcodePolicy, Quirks, MEMORY_SIZE, SCRIPT_ADDR};
+use chip8::{Chip8Error, EmuDisplay, Emulator};
+
+mod common;
+
+#[test]
+fn rom_too_large() {
+    let mut emu = Emulator::new(EmuDisplay::new("chip8"), 1428, Quirks::CHIP_48);
+    let capacity = MEMORY_SIZE - SCRIPT_ADDR;
+
+    assert_eq!(emu.load_script(&vec![0; capacity]), Ok(()));
+    assert_eq!(
+        emu.load_script(&vec![0; capacity + 1]),
+        Err(Chip8Error::RomTooLarge {
+            size: capacity + 1,
+            capacity
+        })
+    );
+}
+
+#[test]
+fn memory_out_of_bounds() {
+    // main starts at 0x202, after the jump to it
+    for (source, pc, addr) in [
+        (": main i := 0xFFE save v3 exit", 0x204, 0x1001),
+        (": main i := 0xFFF load v1 exit", 0x204, 0x1000),
+        (": main i := 0xFFE bcd v0 exit", 0x204, 0x1000),
+        (": main i := 0xFFC sprite v0 v0 8 exit", 0x204, 0x1003),
+        (": main jump 0xFFF", 0xFFF, 0x1000),
+    ] {
+        let mut emu = common::emulator(source, Quirks::CHIP_48);
+        let mut result = Ok(());
+
+        while result.is_ok() && !emu.halted {
+            result = emu.step().map(|_| ());
+        }
+
+        // the program counter is left on the instruction that failed
+        assert_eq!(
+            result,
+            Err(Chip8Error::MemoryOutOfBounds { pc, addr }),
+            "{source}"
+        );
+        assert!(emu.halted, "{source}");
+        assert_eq!(emu.counter, pc, "{source}");
+    }
+}
+
+#[test]
+fn unknown_opcodes() {
+    // 5XY1 isn't an instruction
+    let source = ": main 0x5A 0x01 v0 := 1 exit";
+
+    for (policy, logged) in [(OpcodePolicy::Ignore, 0), (OpcodePolicy::Log, 1)] {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&messages);
+
+        let mut emu = common::emulator(source, Quirks::CHIP_48);
+        emu.unknown_opcodes = policy;
+        emu.log = Some(Box::new(move |message| {
+            log.lock().unwrap().push(message.to_string())
+        }));
+        common::finish(&mut emu);
+
+        assert_eq!(emu.reg[0], 1, "{policy:?}");
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), logged, "{policy:?}");
+        assert!(messages
+            .iter()
+            .all(|message| message == "unknown opcode 5A01 at 0x202"));
+    }
+
+    let mut emu = common::emulator(source, Quirks::CHIP_48);
+    emu.unknown_opcodes = OpcodePolicy::Halt;
+    emu.step().unwrap();
+
+    // the program counter is left on the bad word
+    assert_eq!(
+        emu.step(),
+        Err(Chip8Error::UnknownOpcode {
+            pc: 0x202,
+            opcode: 0x5A01
+        })
+    );
+    assert!(emu.halted);
+    assert_eq!(emu.counter, 0x202);
+    assert_eq!(emu.reg[0], 0);
+
+    assert_eq!(OpcodePolicy::from_name("HALT"), Some(OpcodePolicy::Halt));
+    assert_eq!(OpcodePolicy::from_name("trap"), None);
+}
+
+#[test]
+fn messages() {
+    assert_eq!(
+        Chip8Error::MemoryOutOfBounds {
+            pc: 0x204,
+            addr: 0x1001
+        }
+        .to_string(),
+        "instruction at 0x204 accessed 0x1001, past the end of memory"
+    );
+    assert_eq!(
+        Chip8Error::RomTooLarge {
+            size: 4000,
+            capacity: 3584
+        }
+        .to_string(),
+        "ROM is 4000 bytes, but only 3584 bytes fit in memory"
+    );
+    assert_eq!(
+        Chip8Error::UnknownOpcode {
+            pc: 0x202,
+            opcode: 0x5A01
+        }
+        .to_string(),
+        "unknown opcode 5A01 at 0x202"
+    );
+}
+
+#[test]
+fn stack_overflow() {
+    // main calls f, which calls itself forever
+    let source = ": main f : f f";
+
+    for depth in [2, 12, 16] {
+        let quirks = Quirks {
+            stack_depth: depth,
+            ..Quirks::CHIP_48
+        };
+        let mut emu = common::emulator(source, quirks);
+
+        assert_eq!(
+            emu.run_cycles(100),
+            Err(Chip8Error::StackOverflow { pc: 0x204 })
+        );
+        assert_eq!(emu.stack.len(), depth);
+        assert_eq!(emu.counter, 0x204);
+        assert!(emu.halted);
+    }
+
+    // a wrapping stack drops the oldest return address instead
+    let quirks = Quirks {
+        stack_depth: 4,
+        stack_wrap: true,
+        ..Quirks::CHIP_48
+    };
+    let mut emu = common::emulator(source, quirks);
+
+    emu.run_cycles(100).unwrap();
+    assert_eq!(emu.call_stack().collect::<Vec<_>>(), [0x206; 4]);
+}
+
+#[test]
+fn stack_underflow() {
+    let mut emu = common::emulator(": main return", Quirks::CHIP_48);
+
+    assert_eq!(
+        emu.run_cycles(10),
+        Err(Chip8Error::StackUnderflow { pc: 0x202 })
+    );
+    assert!(emu.halted);
+
+    // a wrapping stack carries on past the return
+    let quirks = Quirks {
+        stack_wrap: true,
+        ..Quirks::CHIP_48
+    };
+    let mut emu = common::emulator(": main return v0 := 1 exit", quirks);
+
+    emu.run_cycles(10).unwrap();
+    assert!(emu.halted);
+    assert_eq!(emu.reg[0], 1);
+}
+
+#[test]
+fn call_stack() {
+    let mut emu = common::emulator(
+        "
+: main
+  f
+  exit
+: f
+  g
+  return
+: g
+  exit
+",
+        Quirks::CHIP_48,
+    );
+
+    emu.run_cycles(10).unwrap();
+
+    // innermost first
+    assert_eq!(emu.call_stack().collect::<Vec<_>>(), [0x208, 0x204]);
+}
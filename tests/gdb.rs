@@ -0,0 +1,174 @@
+//! Drives the GDB stub over a loopback connection with a scripted client
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+
+use chip8::debugger::{gdb::GdbStub, Debugger};
+use chip8::emulator::Quirks;
+
+mod common;
+
+const PROGRAM: &str = "
+: main
+  v0 := 1
+  v1 := 2
+  i := 0x300
+  save v1
+  v0 += 4
+  exit
+";
+
+// a minimal RSP client, which checks acknowledgements and checksums
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn send(&mut self, data: &str) -> String {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${data}#{sum:02x}").unwrap();
+
+        assert_eq!(self.byte(), b'+', "stub did not acknowledge `{data}`");
+
+        assert_eq!(self.byte(), b'$');
+        let mut reply = Vec::new();
+
+        loop {
+            match self.byte() {
+                b'#' => break,
+                byte => reply.push(byte),
+            }
+        }
+
+        let sent = [self.byte(), self.byte()];
+        let sent = u8::from_str_radix(std::str::from_utf8(&sent).unwrap(), 16).unwrap();
+        let sum = reply.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        assert_eq!(sent, sum, "bad checksum on reply to `{data}`");
+
+        self.stream.write_all(b"+").unwrap();
+
+        String::from_utf8(reply).unwrap()
+    }
+
+    fn byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+}
+
+// starts a stub debugging `source` on a background thread, returning a client connected to it
+// and the thread, which gives back the debugger once the client detaches
+fn connect(source: &str) -> (Client, JoinHandle<Debugger>) {
+    let emu = common::emulator(source, Quirks::CHIP_48);
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = thread::spawn(move || {
+        let mut debugger = Debugger::new(emu);
+        let (stream, _) = listener.accept().unwrap();
+
+        GdbStub::new(&mut debugger, stream).serve().unwrap();
+        debugger
+    });
+
+    let stream = TcpStream::connect(addr).unwrap();
+    stream.set_nodelay(true).unwrap();
+
+    (Client { stream }, server)
+}
+
+#[test]
+fn scripted_session() {
+    let (mut gdb, server) = connect(PROGRAM);
+
+    assert!(gdb
+        .send("qSupported:swbreak+")
+        .contains("qXfer:features:read+"));
+    assert!(gdb
+        .send("qXfer:features:read:target.xml:0,1000")
+        .contains(r#"<reg name="pc""#));
+    assert_eq!(gdb.send("?"), "S05");
+    assert_eq!(gdb.send("é"), "E01");
+    assert_eq!(gdb.send(""), "");
+
+    // V0 to VF, I, PC at the start of the ROM, SP, DT and ST
+    let regs = gdb.send("g");
+    assert_eq!(regs.len(), (16 + 2 + 2 + 3) * 2);
+    assert_eq!(&regs[32..40], "00000002");
+
+    // the ROM starts with a jump to main, then main begins with 6001
+    assert_eq!(gdb.send("m200,2"), "1202");
+    assert_eq!(gdb.send("m202,2"), "6001");
+    assert_eq!(gdb.send("mffff,2"), "E01");
+    assert_eq!(gdb.send("mffffffffffffffff,1"), "E01");
+    assert_eq!(gdb.send("Mffffffffffffffff,1:00"), "E01");
+
+    assert_eq!(gdb.send("s"), "S05");
+    assert_eq!(gdb.send("p11"), "0202");
+
+    // break after the registers are set, before they are saved
+    assert_eq!(gdb.send("Z0,208,2"), "OK");
+    assert_eq!(gdb.send("c"), "S05");
+    assert_eq!(gdb.send("p11"), "0802");
+    assert_eq!(gdb.send("p0"), "01");
+    assert_eq!(gdb.send("p10"), "0003");
+    assert_eq!(gdb.send("z0,208,2"), "OK");
+
+    // change V1 before it is saved, and watch the write
+    assert_eq!(gdb.send("P1=7f"), "OK");
+    assert_eq!(gdb.send("Z2,301,1"), "OK");
+    assert_eq!(gdb.send("c"), "T05watch:301;");
+    assert_eq!(gdb.send("m300,2"), "017f");
+    assert_eq!(gdb.send("z2,301,1"), "OK");
+
+    // watchpoints have to be in memory
+    assert_eq!(gdb.send("Z2,ffffffffffffffff,2"), "E01");
+    assert_eq!(gdb.send("Z3,300,ffffffffffffffff"), "E01");
+    assert_eq!(gdb.send("Z4,fff,2"), "E01");
+
+    assert_eq!(gdb.send("M300,2:abcd"), "OK");
+    assert_eq!(gdb.send("m300,2"), "abcd");
+
+    assert_eq!(gdb.send("c"), "W00");
+    assert_eq!(gdb.send("p0"), "05");
+    assert_eq!(gdb.send("D"), "OK");
+
+    let debugger = server.join().unwrap();
+    assert!(debugger.emu.halted);
+    assert_eq!(debugger.emu.reg[1], 0x7F);
+}
+
+#[test]
+fn breakpoint_on_the_last_polled_instruction() {
+    // the stub checks for interrupts every 4096 instructions, so make the breakpoint the
+    // 4096th: the jump to main, setting v0, then 46 runs of the 89 instruction loop body
+    let padding = "v1 += 1\n".repeat(86);
+    let source = format!(
+        ": main
+  v0 := 0
+  loop
+    v0 += 1
+    {padding}
+    while v0 != 46
+  again
+: target
+  exit"
+    );
+
+    let (mut gdb, server) = connect(&source);
+
+    let target = 0x204 + 2 * (86 + 4);
+    assert_eq!(gdb.send(&format!("Z0,{target:x},2")), "OK");
+    assert_eq!(gdb.send("c"), "S05");
+    assert_eq!(
+        gdb.send("p11"),
+        format!("{:04x}", (target as u16).swap_bytes())
+    );
+    assert_eq!(gdb.send("p0"), "2e");
+    assert_eq!(gdb.send("D"), "OK");
+
+    server.join().unwrap();
+}
@@ -0,0 +1,202 @@
+//! Runs every ROM in the ROM directories and compares the final display against a golden image
+//!
+//! Golden images are ASCII art in `tests/golden/<dir>/<rom>.txt`, followed by an `error:` line if
+//! the ROM crashed, so a ROM that starts or stops crashing fails too. Input can be scripted in
+//! `tests/golden/<dir>/<rom>.keys`, in the format read by [`ScriptedKeypad`]. Run with
+//! `UPDATE_GOLDEN=1` to write the golden images from the current output instead of checking them.
+//!
+//! Mismatches are saved to `target/tmp/golden` as PNGs of the expected display, the actual
+//! display and their differences side by side.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8::emulator::Quirks;
+use chip8::frontend::{ScriptedKeypad, PALETTE};
+use chip8::image::{self, ImageFormat};
+
+mod common;
+
+// directories of ROMs to check, relative to the crate root
+const ROM_DIRS: &[&str] = &["scripts"];
+
+// how long each ROM runs for
+const CYCLES: u64 = 100_000;
+
+// extra colors in diff images, for pixels only lit in the expected or actual display
+const MISSING: u8 = 4;
+const EXTRA: u8 = 5;
+const SEPARATOR: u8 = 6;
+const DIFF_PALETTE: [u32; 7] = [
+    PALETTE[0],
+    PALETTE[1],
+    PALETTE[2],
+    PALETTE[3],
+    0x00_FF_40_40,
+    0x00_40_FF_40,
+    0x00_40_40_FF,
+];
+
+// how many times larger diff images are drawn, so they're easier to look at
+const SCALE: usize = 4;
+
+#[test]
+fn golden_images() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+
+    for dir in ROM_DIRS {
+        let golden_dir = root.join("tests/golden").join(dir);
+
+        for rom in roms(&root.join(dir)) {
+            let name = rom.file_stem().unwrap().to_string_lossy().into_owned();
+            let golden = golden_dir.join(format!("{name}.txt"));
+            let keys = read_keys(&golden_dir.join(format!("{name}.keys")));
+
+            let actual = run(&rom, keys);
+
+            if update {
+                fs::create_dir_all(&golden_dir).unwrap();
+                fs::write(&golden, &actual).unwrap();
+                continue;
+            }
+
+            let Ok(expected) = fs::read_to_string(&golden) else {
+                failures.push(format!(
+                    "{dir}/{name}: no golden image, run with UPDATE_GOLDEN=1 to create it"
+                ));
+                continue;
+            };
+
+            if expected != actual {
+                let diff = write_diff(&format!("{dir}-{name}"), &expected, &actual);
+                failures.push(format!(
+                    "{dir}/{name}: display or error differs, see {diff}\n  expected {}\n  actual   {}",
+                    error_line(&expected),
+                    error_line(&actual)
+                ));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+// lists the ROMs in a directory in a stable order
+fn roms(dir: &Path) -> Vec<PathBuf> {
+    let mut roms: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ch8"))
+        .collect();
+
+    roms.sort();
+    roms
+}
+
+// reads the key script for a ROM, a missing script means no input
+fn read_keys(path: &Path) -> ScriptedKeypad {
+    let Ok(script) = fs::read_to_string(path) else {
+        return ScriptedKeypad::default();
+    };
+
+    ScriptedKeypad::parse(&script).unwrap_or_else(|err| panic!("{}: {err}", path.display()))
+}
+
+// runs a ROM for a fixed number of cycles with scripted input, returning the final display as
+// a golden image
+//
+// ROMs that crash, like ones running off the end of memory, stop where they crashed and have
+// the error added after the display
+fn run(rom: &Path, mut keys: ScriptedKeypad) -> String {
+    let mut emu = common::load(&fs::read(rom).unwrap(), Quirks::CHIP_48, 1);
+    let result = emu.run_for(CYCLES, &mut keys);
+
+    let mut image = String::from_utf8(ImageFormat::Ascii.encode(&emu.display)).unwrap();
+
+    if let Err(err) = result {
+        image.push_str(&format!("error: {err}\n"));
+    }
+
+    image
+}
+
+// gets the error recorded in a golden image, for failure messages
+fn error_line(text: &str) -> &str {
+    text.lines()
+        .find(|line| line.starts_with("error:"))
+        .unwrap_or("no error")
+}
+
+// parses a golden image back into colors, leaving out the error
+fn parse(text: &str) -> Vec<Vec<u8>> {
+    text.lines()
+        .filter(|line| !line.starts_with("error:"))
+        .map(|line| {
+            line.chars()
+                .map(|c| {
+                    image::ASCII
+                        .iter()
+                        .position(|&color| color == c)
+                        .unwrap_or(0) as u8
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// saves expected, actual and their differences side by side, returning where they were saved
+fn write_diff(name: &str, expected: &str, actual: &str) -> String {
+    let (expected, actual) = (parse(expected), parse(actual));
+
+    let height = expected.len().max(actual.len());
+    let width = expected
+        .iter()
+        .chain(&actual)
+        .map(Vec::len)
+        .max()
+        .unwrap_or(0);
+
+    let pixel = |rows: &[Vec<u8>], x: usize, y: usize| {
+        rows.get(y).and_then(|row| row.get(x)).copied().unwrap_or(0)
+    };
+
+    // one pixel wide separators between the three panels
+    let full_width = width * 3 + 2;
+    let mut pixels = Vec::with_capacity(full_width * height * SCALE * SCALE);
+
+    for y in 0..height {
+        let mut row = Vec::with_capacity(full_width);
+
+        row.extend((0..width).map(|x| pixel(&expected, x, y)));
+        row.push(SEPARATOR);
+        row.extend((0..width).map(|x| pixel(&actual, x, y)));
+        row.push(SEPARATOR);
+        row.extend(
+            (0..width).map(|x| match (pixel(&expected, x, y), pixel(&actual, x, y)) {
+                (before, after) if before == after => before,
+                (_, 0) => MISSING,
+                _ => EXTRA,
+            }),
+        );
+
+        let row: Vec<u8> = row.into_iter().flat_map(|color| [color; SCALE]).collect();
+
+        for _ in 0..SCALE {
+            pixels.extend_from_slice(&row);
+        }
+    }
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    let path = dir.join(format!("{name}.png"));
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        &path,
+        image::encode_png(full_width * SCALE, height * SCALE, &pixels, &DIFF_PALETTE),
+    )
+    .unwrap();
+
+    path.display().to_string()
+}
@@ -0,0 +1,105 @@
+//! Checks that key maps are resolved and validated the way the docs describe
+#![cfg(feature = "window")]
+
+use chip8::frontend::KeyMap;
+use minifb::Key;
+
+const ROM: u64 = 0x9C5E_3157_DDA5_4FC2;
+
+const CONFIG: &str = "
+# arrows for the usual movement keys
+[keys]
+5 = up, W
+8 = down
+
+[rom 9c5e3157dda54fc2]
+6 = space
+
+[rom 0000000000000001]
+6 = enter
+";
+
+#[test]
+fn global_and_rom_sections() {
+    let keymap = KeyMap::parse(CONFIG, ROM).unwrap();
+
+    assert_eq!(keymap.host_keys(0x5), [Key::Up, Key::W]);
+    assert_eq!(keymap.code(Key::Down), Some(0x8));
+    assert_eq!(keymap.code(Key::S), None);
+    assert_eq!(keymap.code(Key::Space), Some(0x6));
+    assert_eq!(keymap.code(Key::Enter), None);
+
+    // keys that aren't listed keep the default layout
+    assert_eq!(keymap.code(Key::X), Some(0x0));
+    assert_eq!(keymap.code(Key::V), Some(0xF));
+
+    let other = KeyMap::parse(CONFIG, 1).unwrap();
+    assert_eq!(other.code(Key::Enter), Some(0x6));
+    assert_eq!(other.code(Key::Space), None);
+}
+
+#[test]
+fn invalid_maps() {
+    let error = |config: &str| KeyMap::parse(config, ROM).unwrap_err().to_string();
+
+    assert_eq!(error("5 = w"), "line 1: key outside of a section");
+    assert_eq!(
+        error("[keys]\n\ng = w"),
+        "line 3: `g` is not a Chip-8 key, expected 0 to F"
+    );
+    assert_eq!(
+        error("[keys]\n5 = wiggle"),
+        "line 2: unknown host key `wiggle`"
+    );
+    assert!(error("[rom 12]").starts_with("line 1: unknown section"));
+
+    // problems in sections for other ROMs are still caught
+    assert_eq!(
+        error("[rom 0000000000000001]\n1 = nope"),
+        "line 2: unknown host key `nope`"
+    );
+
+    assert_eq!(
+        error("[keys]\n1 = q\n4 = w, q"),
+        "line 3: `q` is used for both key 1 and key 4"
+    );
+    assert_eq!(
+        error("[keys]\n5 = z\n\n[rom 9c5e3157dda54fc2]\n6 = x\n7 = x"),
+        "line 6: `x` is used for both key 6 and key 7"
+    );
+    assert_eq!(
+        error("[keys]\n1 = f5"),
+        "line 2: `f5` can't be used for key 1, since the window uses it for commands"
+    );
+}
+
+#[test]
+fn written_keys_take_host_keys_from_the_default_layout() {
+    // an AZERTY user moving key 5 to z, which key A has by default
+    let keymap = KeyMap::parse("[keys]\n5 = z", ROM).unwrap();
+
+    assert_eq!(keymap.code(Key::Z), Some(0x5));
+    assert_eq!(keymap.host_keys(0xA), []);
+    assert_eq!(keymap.code(Key::W), None);
+    assert_eq!(keymap.code(Key::X), Some(0x0));
+
+    // swapping two keys only needs both written
+    let keymap = KeyMap::parse("[keys]\n1 = 2\n2 = 1", ROM).unwrap();
+
+    assert_eq!(keymap.code(Key::Key1), Some(0x2));
+    assert_eq!(keymap.code(Key::Key2), Some(0x1));
+}
+
+#[test]
+fn rom_keys_take_host_keys_from_the_global_ones() {
+    let keymap = KeyMap::parse("[keys]\n5 = z, up\n\n[rom 9c5e3157dda54fc2]\n6 = z", ROM).unwrap();
+
+    assert_eq!(keymap.code(Key::Z), Some(0x6));
+    assert_eq!(keymap.host_keys(0x5), [Key::Up]);
+
+    // other ROMs keep the global key
+    let keymap = KeyMap::parse("[keys]\n5 = z\n\n[rom 9c5e3157dda54fc2]\n6 = z", 1).unwrap();
+
+    assert_eq!(keymap.code(Key::Z), Some(0x5));
+    assert_eq!(keymap.code(Key::E), Some(0x6));
+}
@@ -0,0 +1,85 @@
+//! Checks that FX0A waits for a fresh press, or a press and release with the key_release quirk
+
+use chip8::emulator::Quirks;
+use chip8::frontend::ScriptedKeypad;
+use chip8::Emulator;
+
+mod common;
+
+const PROGRAM: &str = "
+: main
+  v0 := key
+  v1 := key
+  exit
+";
+
+fn emulator(quirks: Quirks) -> Emulator {
+    let mut emu = common::emulator(PROGRAM, quirks);
+    emu.reg[..2].fill(0xFF);
+
+    emu
+}
+
+// runs whole frames with keys from a script
+fn run_frames(emu: &mut Emulator, keypad: &mut ScriptedKeypad, frames: u64) {
+    let cycles = frames * emu.cycles_per_frame() as u64;
+
+    emu.run_for(cycles, keypad).unwrap();
+}
+
+#[test]
+fn held_keys_only_count_once() {
+    let mut emu = emulator(Quirks::CHIP_48);
+    let mut keypad = ScriptedKeypad::parse("2 5\n20 -\n30 57\n40 -").unwrap();
+
+    run_frames(&mut emu, &mut keypad, 4);
+    assert_eq!(emu.reg[0], 5);
+
+    // still holding 5 doesn't finish the second wait
+    run_frames(&mut emu, &mut keypad, 14);
+    assert_eq!(emu.reg[1], 0xFF);
+    assert!(!emu.halted);
+
+    // 5 was let go, so pressing it again along with 7 counts
+    run_frames(&mut emu, &mut keypad, 14);
+    assert_eq!(emu.reg[1], 5);
+    assert!(emu.halted);
+}
+
+#[test]
+fn keys_held_before_waiting_are_ignored() {
+    let mut emu = emulator(Quirks::CHIP_48);
+
+    // A is held from the start, then 3 is pressed while it's still down
+    let mut keypad = ScriptedKeypad::parse("0 a\n10 3a\n20 a").unwrap();
+
+    run_frames(&mut emu, &mut keypad, 5);
+    assert_eq!(emu.reg[0], 0xFF);
+
+    run_frames(&mut emu, &mut keypad, 10);
+    assert_eq!(emu.reg[0], 3);
+}
+
+#[test]
+fn release_quirk_waits_for_release() {
+    let mut emu = emulator(Quirks::COSMAC_VIP);
+    let mut keypad = ScriptedKeypad::parse("2 5\n10 -\n20 8\n30 -").unwrap();
+
+    run_frames(&mut emu, &mut keypad, 5);
+    assert_eq!(emu.reg[0], 0xFF);
+
+    run_frames(&mut emu, &mut keypad, 10);
+    assert_eq!(emu.reg[0], 5);
+
+    // the wait carries over in save states
+    run_frames(&mut emu, &mut keypad, 10);
+    let state = emu.save_state();
+    let mut copy = emulator(Quirks::COSMAC_VIP);
+    copy.load_state(&state).unwrap();
+    assert_eq!(copy.key_wait, emu.key_wait);
+    assert_eq!(copy.key_wait.unwrap().pressed, Some(8));
+
+    run_frames(&mut emu, &mut keypad, 10);
+    assert_eq!(emu.reg[1], 8);
+    assert!(emu.halted);
+}
@@ -0,0 +1,114 @@
+//! Records a run with scripted input and checks that playing it back gives the same result
+
+use chip8::emulator::{Quirks, XO_CHIP_MEMORY_SIZE};
+use chip8::frontend::{Headless, Keypad, Movie, Playback, Recorder};
+use chip8::{assembler, Emulator};
+
+mod common;
+
+// draws a random digit wherever a key is pressed, forever
+const PROGRAM: &str = "
+: main
+  loop
+    v0 := key
+    v1 := random 0x0F
+    i := hex v1
+    v2 := random 0x1F
+    sprite v0 v2 5
+  again
+";
+
+// a keypad that holds each key in turn for a few frames, then runs out
+struct Script {
+    frame: usize,
+}
+
+impl Keypad for Script {
+    fn update(&mut self) {
+        self.frame += 1;
+    }
+
+    fn snapshot(&self) -> [bool; 16] {
+        let mut keys = [false; 16];
+
+        // every key goes down for 3 frames then up for 3
+        if self.frame % 6 < 3 {
+            keys[self.frame / 6 % 16] = true;
+        }
+
+        keys
+    }
+
+    fn is_finished(&self) -> bool {
+        self.frame >= 300
+    }
+}
+
+fn display(emu: &Emulator) -> Vec<u8> {
+    emu.display.pixels().collect()
+}
+
+#[test]
+fn playback_matches_recording() {
+    let rom = assembler::assemble(PROGRAM).unwrap();
+
+    let mut recorded = common::load(&rom, Quirks::CHIP_48, 77);
+    let movie = Movie::new(77, &rom, &recorded);
+    let mut recorder = Recorder::new(Script { frame: 0 }, movie);
+    recorded
+        .main_loop(&mut Headless::new(), &mut recorder)
+        .unwrap();
+
+    // saving and loading shouldn't lose anything
+    let movie = Movie::decode(&recorder.movie.encode()).unwrap();
+    assert_eq!(movie, recorder.movie);
+    assert_eq!(movie.frames.len(), 300);
+    assert_eq!(movie.rom_hash, Movie::hash_rom(&rom));
+
+    let mut played = common::load(&rom, Quirks::CHIP_48, movie.seed);
+    movie.check(&played).unwrap();
+
+    played
+        .main_loop(&mut Headless::new(), &mut Playback::new(movie))
+        .unwrap();
+
+    assert!(display(&recorded).contains(&1));
+    assert_eq!(display(&played), display(&recorded));
+    assert_eq!(played.reg, recorded.reg);
+    assert_eq!(played.counter, recorded.counter);
+}
+
+#[test]
+fn settings_must_match() {
+    let rom = assembler::assemble(PROGRAM).unwrap();
+    let movie = Movie::new(1, &rom, &common::load(&rom, Quirks::CHIP_48, 1));
+
+    // the settings survive saving and loading
+    let movie = Movie::decode(&movie.encode()).unwrap();
+    assert_eq!(movie.quirks, Quirks::CHIP_48);
+    assert_eq!(movie.memory_size, 4096);
+    assert_eq!(movie.cycles_per_frame, 11);
+
+    let error = |emu: &Emulator| movie.check(emu).unwrap_err().to_string();
+
+    assert_eq!(
+        error(&common::load(&rom, Quirks::COSMAC_VIP, 1)),
+        "movie was recorded with a different set of quirks"
+    );
+    assert_eq!(
+        error(&common::load_sized(
+            &rom,
+            Quirks::CHIP_48,
+            1,
+            XO_CHIP_MEMORY_SIZE
+        )),
+        "movie was recorded with a different memory size"
+    );
+
+    let mut faster = common::load(&rom, Quirks::CHIP_48, 1);
+    faster.tick_us = 100;
+    assert_eq!(
+        error(&faster),
+        "movie was recorded with a different number of instructions per frame"
+    );
+}
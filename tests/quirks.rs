@@ -0,0 +1,134 @@
+//! Checks that each quirk changes the instructions it covers, and that the presets pick them
+
+use chip8::emulator::Quirks;
+
+mod common;
+
+#[test]
+fn presets_by_name() {
+    assert_eq!(Quirks::from_name("vip"), Some(Quirks::COSMAC_VIP));
+    assert_eq!(Quirks::from_name("CHIP48"), Some(Quirks::CHIP_48));
+    assert_eq!(Quirks::from_name("schip"), Some(Quirks::SUPER_CHIP));
+    assert_eq!(Quirks::from_name("xochip"), Some(Quirks::XO_CHIP));
+    assert_eq!(Quirks::from_name("chip8"), None);
+}
+
+#[test]
+fn default_jumps_from_v0() {
+    // V0 + 0x310 lands on 0x312, V3 + 0x310 would land on 0x313
+    let source = "
+: main
+  v0 := 2
+  v3 := 3
+  jump0 0x310
+";
+    let mut emu = common::emulator(source, Quirks::DEFAULT);
+    emu.run_cycles(4).unwrap();
+    assert_eq!(emu.counter, 0x312);
+
+    let mut emu = common::emulator(source, Quirks::CHIP_48);
+    emu.run_cycles(4).unwrap();
+    assert_eq!(emu.counter, 0x313);
+
+    // everything else matches CHIP-48
+    assert_eq!(
+        Quirks {
+            jump: true,
+            ..Quirks::DEFAULT
+        },
+        Quirks::CHIP_48
+    );
+}
+
+#[test]
+fn shift() {
+    let source = "
+: main
+  v0 := 0x10
+  v1 := 0x03
+  v0 >>= v1
+  exit
+";
+
+    // the VIP copies VY into VX before shifting
+    let emu = common::run(source, Quirks::COSMAC_VIP);
+    assert_eq!((emu.reg[0], emu.reg[0xF]), (0x01, 1));
+
+    let emu = common::run(source, Quirks::CHIP_48);
+    assert_eq!((emu.reg[0], emu.reg[0xF]), (0x08, 0));
+}
+
+#[test]
+fn memory() {
+    let source = "
+: main
+  i := 0x300
+  save v3
+  exit
+";
+
+    assert_eq!(common::run(source, Quirks::COSMAC_VIP).index, 0x304);
+    assert_eq!(common::run(source, Quirks::CHIP_48).index, 0x300);
+}
+
+#[test]
+fn logic() {
+    let source = "
+: main
+  vf := 7
+  v0 |= v1
+  exit
+";
+
+    assert_eq!(common::run(source, Quirks::COSMAC_VIP).reg[0xF], 0);
+    assert_eq!(common::run(source, Quirks::CHIP_48).reg[0xF], 7);
+}
+
+#[test]
+fn clip() {
+    // a full row drawn 4 pixels from the right edge
+    let source = "
+: main
+  i := row
+  v0 := 60
+  sprite v0 v1 1
+  exit
+: row
+  0xFF
+";
+
+    let emu = common::run(source, Quirks::CHIP_48);
+    assert_eq!(common::pixel(&emu, 63, 0), 1);
+    assert_eq!(common::pixel(&emu, 0, 0), 0);
+
+    let emu = common::run(source, Quirks::XO_CHIP);
+    assert_eq!(common::pixel(&emu, 63, 0), 1);
+    assert_eq!(common::pixel(&emu, 0, 0), 1);
+}
+
+#[test]
+fn display_wait() {
+    // two sprites, the second has to wait for the next frame on the VIP
+    let source = "
+: main
+  i := row
+  sprite v0 v1 1
+  sprite v0 v1 1
+  exit
+: row
+  0xFF
+";
+    let mut emu = common::emulator(source, Quirks::COSMAC_VIP);
+    emu.run_cycles(20).unwrap();
+    assert_eq!(emu.counter, 0x206);
+    assert_eq!(common::pixel(&emu, 0, 0), 1);
+
+    emu.tick_timers();
+    emu.run_cycles(2).unwrap();
+    assert!(emu.halted);
+    assert_eq!(common::pixel(&emu, 0, 0), 0);
+
+    let mut emu = common::emulator(source, Quirks::CHIP_48);
+    emu.run_cycles(20).unwrap();
+    assert!(emu.halted);
+}
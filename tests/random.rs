@@ -0,0 +1,71 @@
+//! Checks that CXNN only depends on the emulator's random source
+
+use chip8::emulator::{Quirks, Sequence, XorShift};
+use chip8::Emulator;
+
+mod common;
+
+// fills V0 to V7 with random bytes, then stops
+const PROGRAM: &str = "
+: main
+  v0 := random 0xFF
+  v1 := random 0xFF
+  v2 := random 0xFF
+  v3 := random 0xFF
+  v4 := random 0x0F
+  v5 := random 0x0F
+  v6 := random 0x0F
+  v7 := random 0x0F
+  exit
+";
+
+// runs the program to the end, returning the numbers it picked
+fn picked(mut emu: Emulator) -> [u8; 8] {
+    common::finish(&mut emu);
+
+    emu.reg[..8].try_into().unwrap()
+}
+
+#[test]
+fn fixed_sequence() {
+    let mut emu = common::emulator(PROGRAM, Quirks::CHIP_48);
+    emu.rng = Box::new(Sequence::new([0x12, 0x34, 0xAB]));
+
+    assert_eq!(
+        picked(emu),
+        [0x12, 0x34, 0xAB, 0x12, 0x04, 0x0B, 0x02, 0x04]
+    );
+}
+
+#[test]
+fn same_seed_same_numbers() {
+    let mut first = common::emulator(PROGRAM, Quirks::CHIP_48);
+    first.rng = Box::new(XorShift::new(1234));
+
+    let mut second = common::emulator(PROGRAM, Quirks::CHIP_48);
+    second.rng = Box::new(XorShift::new(1234));
+
+    let mut other = common::emulator(PROGRAM, Quirks::CHIP_48);
+    other.rng = Box::new(XorShift::new(4321));
+
+    let numbers = picked(first);
+    assert_eq!(numbers, picked(second));
+    assert_ne!(numbers, picked(other));
+}
+
+#[test]
+fn save_states_keep_the_generator() {
+    let mut emu = common::emulator(PROGRAM, Quirks::CHIP_48);
+    emu.rng = Box::new(XorShift::new(99));
+
+    // stop partway through, after the jump to main and two random numbers
+    for _ in 0..3 {
+        emu.step().unwrap();
+    }
+
+    let state = emu.save_state();
+    let mut copy = common::emulator(PROGRAM, Quirks::CHIP_48);
+    copy.load_state(&state).unwrap();
+
+    assert_eq!(picked(emu), picked(copy));
+}
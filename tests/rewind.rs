@@ -0,0 +1,105 @@
+//! Checks that rewinding steps back through exactly the frames that were recorded
+
+use chip8::assembler;
+use chip8::emulator::{Quirks, Rewind};
+
+mod common;
+
+#[test]
+fn buffer() {
+    let states: Vec<Vec<u8>> = vec![
+        vec![
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20,
+        ],
+        vec![
+            1, 2, 3, 4, 0, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 0, 20,
+        ],
+        vec![1, 2, 3],
+        vec![9; 30],
+        vec![],
+        vec![9; 30],
+    ];
+
+    let mut rewind = Rewind::new(10);
+
+    for state in &states {
+        rewind.push(state.clone());
+    }
+
+    assert_eq!(rewind.len(), states.len() - 1);
+
+    for state in states.iter().rev().skip(1) {
+        assert_eq!(rewind.pop(), Some(state.as_slice()));
+    }
+
+    assert_eq!(rewind.pop(), None);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn capacity() {
+    let mut rewind = Rewind::new(3);
+
+    for i in 0..10 {
+        rewind.push(vec![i; 4]);
+    }
+
+    // only the 3 frames before the newest are kept
+    assert_eq!(rewind.len(), 3);
+    assert_eq!(rewind.pop(), Some([8; 4].as_slice()));
+    assert_eq!(rewind.pop(), Some([7; 4].as_slice()));
+    assert_eq!(rewind.pop(), Some([6; 4].as_slice()));
+    assert_eq!(rewind.pop(), None);
+
+    // with no capacity there is nothing to go back to
+    let mut rewind = Rewind::new(0);
+    rewind.push(vec![1]);
+    rewind.push(vec![2]);
+    assert_eq!(rewind.pop(), None);
+
+    rewind.clear();
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn emulator_steps_back() {
+    let rom = assembler::assemble(
+        "
+: main
+  i := dot
+  loop
+    v1 := random 0x1F
+    sprite v0 v1 1
+    v0 += 1
+  again
+: dot
+  0x80
+",
+    )
+    .unwrap();
+
+    let mut emu = common::load(&rom, Quirks::CHIP_48, 3);
+    emu.rewind = Some(Rewind::new(60));
+
+    let mut frames = Vec::new();
+
+    for _ in 0..20 {
+        emu.run_frame().unwrap();
+        emu.record_frame();
+        frames.push(emu.save_state());
+    }
+
+    // the newest frame is the current one, so stepping back starts from the one before
+    for frame in frames.iter().rev().skip(1) {
+        assert!(emu.step_back());
+        assert_eq!(&emu.save_state(), frame);
+    }
+
+    assert!(!emu.step_back());
+
+    // running again from the oldest frame repeats the same frames
+    for frame in &frames[1..] {
+        emu.run_frame().unwrap();
+        assert_eq!(&emu.save_state(), frame);
+    }
+}
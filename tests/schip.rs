@@ -0,0 +1,169 @@
+//! Checks the SUPER-CHIP 1.1 instructions, with the display and memory they affect
+
+use chip8::emulator::{Quirks, BIG_FONT_ADDR};
+use chip8::Emulator;
+
+mod common;
+
+// every lit pixel on the display
+fn lit(emu: &Emulator) -> Vec<(usize, usize)> {
+    let (width, _) = emu.display.dimensions();
+
+    emu.display
+        .pixels()
+        .enumerate()
+        .filter(|(_, color)| *color != 0)
+        .map(|(i, _)| (i % width, i / width))
+        .collect()
+}
+
+#[test]
+fn resolution() {
+    let emu = common::run(": main hires exit", Quirks::SUPER_CHIP);
+    assert_eq!(emu.display.dimensions(), (128, 64));
+
+    // switching clears the display
+    let emu = common::run(
+        "
+: main
+  hires
+  i := dot
+  sprite v0 v0 1
+  lores
+  exit
+: dot
+  0x80
+",
+        Quirks::SUPER_CHIP,
+    );
+    assert_eq!(emu.display.dimensions(), (64, 32));
+    assert!(lit(&emu).is_empty());
+}
+
+#[test]
+fn large_sprites() {
+    let source = "
+: main
+  hires
+  i := square
+  v0 := 120
+  sprite v0 v1 0
+  vf := 5
+  sprite v0 v1 0
+  exit
+: square
+  0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF
+  0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF 0xFF
+";
+
+    // drawing it twice erases it, flagging the collision
+    let emu = common::run(source, Quirks::SUPER_CHIP);
+    assert!(lit(&emu).is_empty());
+    assert_eq!(emu.reg[0xF], 1);
+
+    // only drawn once, the 16x16 square is clipped by the right edge
+    let emu = common::run(
+        &source.replacen("  vf := 5\n  sprite v0 v1 0\n", "", 1),
+        Quirks::SUPER_CHIP,
+    );
+    let pixels = lit(&emu);
+
+    assert_eq!(pixels.len(), 8 * 16);
+    assert!(pixels.contains(&(127, 15)));
+    assert!(!pixels.contains(&(120, 16)));
+    assert_eq!(emu.reg[0xF], 0);
+}
+
+#[test]
+fn scrolling() {
+    let emu = common::run(
+        "
+: main
+  hires
+  i := dot
+  v0 := 8
+  sprite v0 v0 1
+  scroll-down 3
+  scroll-right
+  scroll-right
+  scroll-left
+  scroll-up 1
+  exit
+: dot
+  0x80
+",
+        Quirks::SUPER_CHIP,
+    );
+
+    assert_eq!(lit(&emu), [(12, 10)]);
+
+    // pixels scrolled past an edge are gone, rather than wrapping around
+    let emu = common::run(
+        "
+: main
+  i := dot
+  sprite v0 v0 1
+  scroll-left
+  scroll-up 1
+  scroll-right
+  scroll-down 1
+  exit
+: dot
+  0x80
+",
+        Quirks::SUPER_CHIP,
+    );
+
+    assert!(lit(&emu).is_empty());
+}
+
+#[test]
+fn big_font() {
+    let emu = common::run(
+        "
+: main
+  v0 := 0x17
+  i := bighex v0
+  exit
+",
+        Quirks::SUPER_CHIP,
+    );
+
+    assert_eq!(emu.index, BIG_FONT_ADDR + 7 * 10);
+    assert_eq!(emu.memory[emu.index..emu.index + 2], [0xFF, 0xFF]);
+}
+
+#[test]
+fn flags() {
+    let emu = common::run(
+        "
+: main
+  v0 := 1
+  v1 := 2
+  v2 := 3
+  v3 := 4
+  saveflags v2
+  v0 := 0
+  v1 := 0
+  v2 := 0
+  v3 := 0
+  loadflags v3
+  exit
+",
+        Quirks::SUPER_CHIP,
+    );
+
+    assert_eq!(emu.flags[..4], [1, 2, 3, 0]);
+    assert_eq!(emu.reg[..4], [1, 2, 3, 0]);
+}
+
+#[test]
+fn exit_stays_put() {
+    let mut emu = common::emulator(": main exit", Quirks::SUPER_CHIP);
+
+    emu.run_cycles(10).unwrap();
+
+    assert!(emu.halted);
+    assert_eq!(emu.counter, 0x202);
+    assert_eq!(emu.step().unwrap().pc_after, 0x202);
+}
@@ -0,0 +1,181 @@
+//! Checks that save states restore the whole machine and reject anything they can't read
+
+use std::sync::{Arc, Mutex};
+
+use chip8::emulator::{Quirks, StateError, STATE_VERSION};
+use chip8::frontend::Command;
+use chip8::{assembler, EmuDisplay, Emulator, Frontend};
+
+mod common;
+
+// draws, calls a subroutine and scrolls in a loop, to change as much of the machine as it can
+const PROGRAM: &str = "
+: main
+  hires
+  v0 := 30
+  delay := v0
+  buzzer := v0
+  i := dot
+  loop
+    draw
+    scroll-right
+    v1 += 3
+  again
+: draw
+  v2 := random 0x3F
+  sprite v1 v2 2
+  saveflags v2
+  return
+: dot
+  0xC0 0xC0
+";
+
+fn emulator(quirks: Quirks, seed: u64) -> Emulator {
+    let rom = assembler::assemble(PROGRAM).unwrap();
+
+    common::load(&rom, quirks, seed)
+}
+
+#[test]
+fn round_trip() {
+    let mut emu = emulator(Quirks::SUPER_CHIP, 7);
+
+    // stop inside the subroutine
+    emu.run_cycles(103).unwrap();
+
+    let state = emu.save_state();
+
+    // everything in the state comes from it, not from the emulator it's loaded into
+    let mut copy = emulator(Quirks::COSMAC_VIP, 8);
+    copy.load_state(&state).unwrap();
+
+    assert_eq!(copy.save_state(), state);
+    assert_eq!(copy.quirks, Quirks::SUPER_CHIP);
+    assert_eq!(copy.counter, emu.counter);
+    assert_eq!(copy.call_stack().collect::<Vec<_>>(), [0x20E]);
+
+    // and both carry on the same way, random numbers included
+    emu.run_cycles(500).unwrap();
+    copy.run_cycles(500).unwrap();
+
+    assert_eq!(copy.save_state(), emu.save_state());
+}
+
+#[test]
+fn files() {
+    let path = std::env::temp_dir().join(format!("chip8-state-{}.state", std::process::id()));
+
+    let mut emu = emulator(Quirks::SUPER_CHIP, 7);
+    emu.run_cycles(50).unwrap();
+    emu.save_state_file(&path).unwrap();
+
+    let mut copy = emulator(Quirks::SUPER_CHIP, 7);
+    copy.load_state_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(copy.save_state(), emu.save_state());
+    assert!(matches!(
+        copy.load_state_file(&path),
+        Err(StateError::Io(_))
+    ));
+}
+
+#[test]
+fn rejected_states() {
+    let mut emu = emulator(Quirks::SUPER_CHIP, 7);
+    emu.run_cycles(50).unwrap();
+
+    let state = emu.save_state();
+    let error = |state: &[u8]| {
+        emulator(Quirks::SUPER_CHIP, 7)
+            .load_state(state)
+            .unwrap_err()
+    };
+
+    let mut newer = state.clone();
+    newer[4..6].copy_from_slice(&(STATE_VERSION + 1).to_be_bytes());
+
+    assert!(matches!(
+        error(&newer),
+        StateError::UnsupportedVersion(version) if version == STATE_VERSION + 1
+    ));
+    assert_eq!(
+        error(&newer).to_string(),
+        format!(
+            "save state is version {}, but only version {STATE_VERSION} is supported",
+            STATE_VERSION + 1
+        )
+    );
+
+    assert!(matches!(error(b"PNG!"), StateError::NotAState));
+    assert!(matches!(
+        error(&state[..state.len() - 1]),
+        StateError::Truncated
+    ));
+
+    let mut longer = state.clone();
+    longer.push(0);
+    assert!(matches!(error(&longer), StateError::Invalid("length")));
+
+    // a failed load leaves the emulator alone
+    let mut other = emulator(Quirks::SUPER_CHIP, 7);
+    let before = other.save_state();
+
+    assert!(other.load_state(&longer).is_err());
+    assert_eq!(other.save_state(), before);
+}
+
+// loads a save state on the first frame, then closes
+struct LoadOnce {
+    frames: u32,
+}
+
+impl Frontend for LoadOnce {
+    fn refresh(&mut self, _display: &EmuDisplay) {}
+
+    fn commands(&mut self) -> Vec<Command> {
+        match self.frames {
+            0 => vec![Command::LoadState(1)],
+            _ => Vec::new(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.frames < 2
+    }
+
+    fn wait_frame(&mut self) {
+        self.frames += 1;
+    }
+}
+
+#[test]
+fn failed_commands_are_logged() {
+    let path = std::env::temp_dir().join(format!("chip8-missing-{}.ch8", std::process::id()));
+
+    for (state_path, expected) in [
+        (None, "save states need a state path".to_string()),
+        (
+            Some(path.clone()),
+            format!("{}: ", path.with_extension("1.state").display()),
+        ),
+    ] {
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&messages);
+
+        let mut emu = emulator(Quirks::SUPER_CHIP, 7);
+        emu.state_path = state_path;
+        emu.log = Some(Box::new(move |message| {
+            log.lock().unwrap().push(message.to_string())
+        }));
+
+        // the program keeps running
+        emu.main_loop(&mut LoadOnce { frames: 0 }, &mut [false; 16])
+            .unwrap();
+        assert!(!emu.halted);
+
+        let messages = messages.lock().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with(&expected), "{}", messages[0]);
+    }
+}
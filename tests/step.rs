@@ -0,0 +1,76 @@
+//! Checks what single steps and frames report about the instructions they execute
+
+use chip8::emulator::Quirks;
+use chip8::StepInfo;
+
+mod common;
+
+#[test]
+fn steps() {
+    let mut emu = common::emulator(
+        "
+: main
+  v0 := 1
+  if v0 != 1 then v1 := 2
+  i := dot
+  sprite v0 v0 1
+  exit
+: dot
+  0x80
+",
+        Quirks::CHIP_48,
+    );
+
+    let step = |pc_before, opcode, pc_after, display_changed| StepInfo {
+        opcode,
+        pc_before,
+        pc_after,
+        display_changed,
+    };
+
+    // a jump, a skip over the next instruction, a draw, and an exit that stays put
+    let expected = [
+        step(0x200, 0x1202, 0x202, false),
+        step(0x202, 0x6001, 0x204, false),
+        step(0x204, 0x3001, 0x208, false),
+        step(0x208, 0xA20E, 0x20A, false),
+        step(0x20A, 0xD001, 0x20C, true),
+        step(0x20C, 0x00FD, 0x20C, false),
+        step(0x20C, 0x00FD, 0x20C, false),
+    ];
+
+    for expected in expected {
+        assert_eq!(emu.step(), Ok(expected));
+    }
+
+    assert_eq!(emu.reg[1], 0);
+    assert!(emu.halted);
+}
+
+#[test]
+fn frames() {
+    let increments = "v0 += 1\n".repeat(28);
+    let mut emu = common::emulator(
+        &format!(": main {increments} i := dot sprite v0 v0 1 exit : dot 0x80"),
+        Quirks::CHIP_48,
+    );
+
+    // 700 instructions a second is 11 a frame, the first is the jump to main
+    assert_eq!(emu.cycles_per_frame(), 11);
+    assert_eq!(emu.run_frame(), Ok(false));
+    assert_eq!(emu.reg[0], 10);
+    assert_eq!(emu.timer, 254);
+
+    assert_eq!(emu.run_frame(), Ok(false));
+    assert_eq!(emu.reg[0], 21);
+
+    // the frame with the sprite in it changes the display, and stops at the exit
+    assert_eq!(emu.run_frame(), Ok(true));
+    assert_eq!(emu.reg[0], 28);
+    assert!(emu.halted);
+
+    emu.tick_us = 16666;
+    assert_eq!(emu.cycles_per_frame(), 1);
+    emu.tick_us = 0;
+    assert_eq!(emu.cycles_per_frame(), 16666);
+}
@@ -0,0 +1,121 @@
+//! Checks the text and binary trace formats
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use chip8::emulator::{Quirks, Trace, TraceFormat};
+
+mod common;
+
+const PROGRAM: &str = "
+: main
+  v0 := 0x12
+  i := 0x345
+  v0 += 1
+  exit
+";
+
+// a writer that can still be read after the trace that owns it is finished
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// runs a program until it exits or fails with a trace, returning everything that was written
+fn trace(source: &str, format: TraceFormat) -> Vec<u8> {
+    let mut emu = common::emulator(source, Quirks::XO_CHIP);
+    let out = Shared::default();
+
+    emu.trace = Some(Trace::new(out.clone(), format));
+
+    while !emu.halted {
+        let _ = emu.step();
+    }
+
+    emu.trace.take().unwrap().finish().unwrap();
+
+    let written = out.0.lock().unwrap().clone();
+    written
+}
+
+#[test]
+fn text() {
+    let text = String::from_utf8(trace(PROGRAM, TraceFormat::Text)).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[0],
+        "0000000001 0200 1202 JP 0x202             \
+         00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I=0000"
+    );
+    assert_eq!(
+        lines[3],
+        "0000000004 0206 7001 ADD V0, 0x01         \
+         13 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I=0345"
+    );
+    assert!(lines[4].starts_with("0000000005 0208 00FD EXIT"));
+}
+
+#[test]
+fn binary() {
+    let binary = trace(PROGRAM, TraceFormat::Binary);
+
+    assert_eq!(binary[..5], *b"C8TR\x02");
+
+    let records: Vec<&[u8]> = binary[5..].chunks(32).collect();
+    assert_eq!(records.len(), 5);
+    assert!(records.iter().all(|record| record.len() == 32));
+
+    let mut expected = Vec::new();
+    expected.extend_from_slice(&4u64.to_be_bytes());
+    expected.extend_from_slice(&[0x02, 0x06, 0x70, 0x01, 0x00, 0x00, 0x13]);
+    expected.extend_from_slice(&[0; 15]);
+    expected.extend_from_slice(&[0x03, 0x45]);
+
+    assert_eq!(records[3], expected);
+}
+
+#[test]
+fn long_index() {
+    let text = String::from_utf8(trace(": main i := long 0xABCD exit", TraceFormat::Text)).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert!(lines[1].starts_with("0000000002 0202 F000 LD I, 0xABCD"));
+    assert!(lines[1].ends_with(" I=ABCD"));
+
+    let binary = trace(": main i := long 0xABCD exit", TraceFormat::Binary);
+    assert_eq!(
+        binary[5 + 32 + 8..][..6],
+        [0x02, 0x02, 0xF0, 0x00, 0xAB, 0xCD]
+    );
+}
+
+#[test]
+fn failing_instruction() {
+    // the save goes past the end of memory, so it is the last instruction executed
+    let text = String::from_utf8(trace(
+        ": main i := 0xFFE v0 := 1 save v3 exit",
+        TraceFormat::Text,
+    ))
+    .unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+
+    assert_eq!(lines.len(), 4);
+    assert!(lines[3].starts_with("0000000004 0206 F355 LD [I], V3"));
+}
+
+#[test]
+fn format_names() {
+    assert_eq!(TraceFormat::from_name("TEXT"), Some(TraceFormat::Text));
+    assert_eq!(TraceFormat::from_name("binary"), Some(TraceFormat::Binary));
+    assert_eq!(TraceFormat::from_name("json"), None);
+}
@@ -0,0 +1,161 @@
+//! Checks the XO-CHIP extensions: bitplanes, long I, register ranges, 64KiB memory and audio
+
+use chip8::emulator::{Quirks, XO_CHIP_MEMORY_SIZE};
+
+mod common;
+
+#[test]
+fn planes() {
+    let emu = common::run_sized(
+        "
+: main
+  i := sprite
+  plane 2
+  sprite v0 v0 1
+  plane 3
+  v0 := 8
+  sprite v0 v1 1
+  exit
+: sprite
+  0x80 0xC0
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+
+    // the first sprite is only on plane 2, the second takes a byte for each plane
+    assert_eq!(common::pixel(&emu, 0, 0), 0b10);
+    assert_eq!(common::pixel(&emu, 8, 0), 0b11);
+    assert_eq!(common::pixel(&emu, 9, 0), 0b10);
+
+    // clearing only affects the selected planes
+    let emu = common::run_sized(
+        "
+: main
+  i := dot
+  plane 3
+  sprite v0 v0 1
+  plane 1
+  clear
+  exit
+: dot
+  0x80 0x80
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+
+    assert_eq!(common::pixel(&emu, 0, 0), 0b10);
+}
+
+#[test]
+fn long_index() {
+    let mut emu = common::emulator_sized(
+        "
+: main
+  i := long 0xABCD
+  load v1
+  exit
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+    emu.memory[0xABCD..0xABCF].copy_from_slice(&[0x12, 0x34]);
+    common::finish(&mut emu);
+
+    assert_eq!(emu.reg[..2], [0x12, 0x34]);
+    assert_eq!(emu.index, 0xABCF);
+
+    // skipping F000 NNNN skips both of its words
+    let emu = common::run_sized(
+        "
+: main
+  if v0 != 0 then i := long 0x4242
+  exit
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+
+    assert_eq!(emu.index, 0);
+}
+
+#[test]
+fn register_ranges() {
+    let emu = common::run_sized(
+        "
+: main
+  v2 := 1
+  v3 := 2
+  v4 := 3
+  i := 0x300
+  save v4 - v2
+  load v5 - v7
+  exit
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+
+    // I doesn't move, and ranges can go backwards
+    assert_eq!(emu.index, 0x300);
+    assert_eq!(emu.memory[0x300..0x303], [3, 2, 1]);
+    assert_eq!(emu.reg[5..8], [3, 2, 1]);
+}
+
+#[test]
+fn index_overflow() {
+    // well past 4KiB is still in memory
+    let emu = common::run_sized(
+        "
+: main
+  i := long 0x2000
+  v0 := 1
+  i += v0
+  exit
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+
+    assert_eq!((emu.index, emu.reg[0xF]), (0x2001, 0));
+
+    let emu = common::run_sized(
+        "
+: main
+  i := long 0xFFFF
+  v0 := 1
+  i += v0
+  exit
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+
+    assert_eq!((emu.index, emu.reg[0xF]), (0x10000, 1));
+}
+
+#[test]
+fn audio() {
+    let emu = common::run_sized(
+        "
+: main
+  i := pattern
+  audio
+  v0 := 112
+  pitch := v0
+  exit
+: pattern
+  0x01 0x23 0x45 0x67 0x89 0xAB 0xCD 0xEF 0xFE 0xDC 0xBA 0x98 0x76 0x54 0x32 0x10
+",
+        Quirks::XO_CHIP,
+        XO_CHIP_MEMORY_SIZE,
+    );
+
+    assert_eq!(emu.audio_pattern[..3], [0x01, 0x23, 0x45]);
+    assert_eq!(emu.audio_pattern[15], 0x10);
+
+    // 48 steps of pitch doubles the rate
+    assert_eq!(emu.pitch, 112);
+    assert!((emu.audio_rate() - 8000.0).abs() < 0.001);
+}